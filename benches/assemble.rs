@@ -0,0 +1,50 @@
+// Throughput of assembling large generated sources, buffered and streamed.
+//
+// Memory only holds 65536 words, so the 100k instructions are spread over four programs of
+// 25k instructions each rather than being one program.
+
+use std::time::Instant;
+
+use lc3_assembler::*;
+
+const PROGRAMS: usize = 4;
+const INSTRUCTIONS: usize = 25_000;
+const RUNS: usize = 5;
+
+// a program of adds and loads, with a backward branch every so often so labels get resolved
+fn source() -> String {
+    let mut source = String::from(".orig x3000\n");
+    for i in 0..INSTRUCTIONS {
+        source += &match i % 4 {
+            0 => format!("L{} ADD R{}, R{}, #{}\n", i, i % 8, (i + 1) % 8, i % 16),
+            1 => format!("    LDR R{}, R6, #{}\n", i % 8, (i % 32) as i32 - 16),
+            2 => format!("    NOT R{}, R{}\n", i % 8, (i + 3) % 8),
+            _ => format!("    BRnzp L{}\n", i.saturating_sub(100) / 4 * 4),
+        };
+    }
+    source + ".end\n"
+}
+
+fn bench(name: &str, source: &str, assemble: impl Fn(&str) -> usize) {
+    let mut best = f64::MAX;
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        let words = (0..PROGRAMS).map(|_| assemble(source)).sum::<usize>();
+        assert_eq!(words, PROGRAMS * (INSTRUCTIONS + 1));
+        best = best.min(start.elapsed().as_secs_f64());
+    }
+
+    let instructions = (PROGRAMS * INSTRUCTIONS) as f64;
+    println!("{}: {:.1} ms, {:.0} instructions/s", name, best * 1000.0, instructions / best);
+}
+
+fn main() {
+    let source = source();
+
+    bench("buffered", &source, |source| assemble(source).unwrap().len());
+    bench("streaming", &source, |source| {
+        let mut words = 0;
+        assemble_streaming(source, Config::default(), |_| words += 1).unwrap();
+        words
+    });
+}
@@ -1,3 +1,4 @@
+.orig x3000
 JSR 3
 LD R1 -4
 LD R2 -4
@@ -24,4 +25,5 @@ BR z 3
 JSRR R3
 ADD R1 R1 1
 BR nzp -5
-JMP R2 
\ No newline at end of file
+JMP R2 
+.END
\ No newline at end of file
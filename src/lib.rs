@@ -0,0 +1,2786 @@
+use std::collections::HashMap;
+
+use num_parse::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    Add,
+    And,
+    Branch,
+    Jump,
+    JumpSubroutine,
+    JumpSubroutineRegister,
+    Load,
+    LoadIndirect,
+    LoadRegister,
+    LoadEffectiveAddress,
+    NoOperation,
+    Not,
+    Return,
+    ReturnInterrupt,
+    Store,
+    StoreIndirect,
+    StoreRegister,
+    Trap,
+    Getc,
+    Out,
+    Puts,
+    In,
+    Putsp,
+    Halt,
+}
+
+impl Instruction {
+    pub const ALL: [Self; 24] = [
+        Self::Add,
+        Self::And,
+        Self::Branch,
+        Self::Jump,
+        Self::JumpSubroutine,
+        Self::JumpSubroutineRegister,
+        Self::Load,
+        Self::LoadIndirect,
+        Self::LoadRegister,
+        Self::LoadEffectiveAddress,
+        Self::NoOperation,
+        Self::Not,
+        Self::Return,
+        Self::ReturnInterrupt,
+        Self::Store,
+        Self::StoreIndirect,
+        Self::StoreRegister,
+        Self::Trap,
+        Self::Getc,
+        Self::Out,
+        Self::Puts,
+        Self::In,
+        Self::Putsp,
+        Self::Halt,
+    ];
+
+    pub fn binary(self) -> u16 {
+        match self {
+            Self::Add => 0b0001,
+            Self::And => 0b0101,
+            Self::Branch => 0b0000,
+            Self::Jump => 0b1100,
+            Self::JumpSubroutine => 0b0100,
+            Self::JumpSubroutineRegister => 0b0100,
+            Self::Load => 0b0010,
+            Self::LoadIndirect => 0b1010,
+            Self::LoadRegister => 0b0110,
+            Self::LoadEffectiveAddress => 0b1110,
+            Self::NoOperation => 0b0000,
+            Self::Not => 0b1001,
+            Self::Return => 0b1100,
+            Self::ReturnInterrupt => 0b1000,
+            Self::Store => 0b0011,
+            Self::StoreIndirect => 0b1011,
+            Self::StoreRegister => 0b0111,
+            Self::Trap => 0b1111,
+            Self::Getc => 0b1111,
+            Self::Out => 0b1111,
+            Self::Puts => 0b1111,
+            Self::In => 0b1111,
+            Self::Putsp => 0b1111,
+            Self::Halt => 0b1111,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Add => "ADD",
+            Self::And => "AND",
+            Self::Branch => "BR",
+            Self::Jump => "JMP",
+            Self::JumpSubroutine => "JSR",
+            Self::JumpSubroutineRegister => "JSRR",
+            Self::Load => "LD",
+            Self::LoadIndirect => "LDI",
+            Self::LoadRegister => "LDR",
+            Self::LoadEffectiveAddress => "LEA",
+            Self::NoOperation => "NOP",
+            Self::Not => "NOT",
+            Self::Return => "RET",
+            Self::ReturnInterrupt => "RTI",
+            Self::Store => "ST",
+            Self::StoreIndirect => "STI",
+            Self::StoreRegister => "STR",
+            Self::Trap => "TRAP",
+            Self::Getc => "GETC",
+            Self::Out => "OUT",
+            Self::Puts => "PUTS",
+            Self::In => "IN",
+            Self::Putsp => "PUTSP",
+            Self::Halt => "HALT",
+        }
+    }
+
+    // the most operands the instruction takes, which is all of them for everything but a branch
+    pub fn num_args(self) -> usize {
+        match self {
+            Self::Add => 3,
+            Self::And => 3,
+            Self::Branch => 2,
+            Self::Jump => 1,
+            Self::JumpSubroutine => 1,
+            Self::JumpSubroutineRegister => 1,
+            Self::Load => 2,
+            Self::LoadIndirect => 2,
+            Self::LoadRegister => 3,
+            Self::LoadEffectiveAddress => 2,
+            Self::NoOperation => 0,
+            Self::Not => 2,
+            Self::Return => 0,
+            Self::ReturnInterrupt => 0,
+            Self::Store => 2,
+            Self::StoreIndirect => 2,
+            Self::StoreRegister => 3,
+            Self::Trap => 1,
+            Self::Getc => 0,
+            Self::Out => 0,
+            Self::Puts => 0,
+            Self::In => 0,
+            Self::Putsp => 0,
+            Self::Halt => 0,
+        }
+    }
+
+    // the fields of the instruction word from the opcode down
+    pub fn encoding(self) -> &'static str {
+        match self {
+            Self::Add => "0001 DR SR1 0 00 SR2, or 0001 DR SR1 1 imm5",
+            Self::And => "0101 DR SR1 0 00 SR2, or 0101 DR SR1 1 imm5",
+            Self::Branch => "0000 n z p PCoffset9",
+            Self::Jump => "1100 000 BaseR 000000",
+            Self::JumpSubroutine => "0100 1 PCoffset11",
+            Self::JumpSubroutineRegister => "0100 0 00 BaseR 000000",
+            Self::Load => "0010 DR PCoffset9",
+            Self::LoadIndirect => "1010 DR PCoffset9",
+            Self::LoadRegister => "0110 DR BaseR offset6",
+            Self::LoadEffectiveAddress => "1110 DR PCoffset9",
+            Self::NoOperation => "0000 000 000000000",
+            Self::Not => "1001 DR SR 111111",
+            Self::Return => "1100 000 111 000000",
+            Self::ReturnInterrupt => "1000 000000000000",
+            Self::Store => "0011 SR PCoffset9",
+            Self::StoreIndirect => "1011 SR PCoffset9",
+            Self::StoreRegister => "0111 SR BaseR offset6",
+            Self::Trap => "1111 0000 trapvect8",
+            Self::Getc => "1111 0000 x20",
+            Self::Out => "1111 0000 x21",
+            Self::Puts => "1111 0000 x22",
+            Self::In => "1111 0000 x23",
+            Self::Putsp => "1111 0000 x24",
+            Self::Halt => "1111 0000 x25",
+        }
+    }
+
+    // the trap vector a trap alias stands for
+    pub fn trap_vector(self) -> Option<u8> {
+        match self {
+            Self::Getc => Some(0x20),
+            Self::Out => Some(0x21),
+            Self::Puts => Some(0x22),
+            Self::In => Some(0x23),
+            Self::Putsp => Some(0x24),
+            Self::Halt => Some(0x25),
+            _ => None,
+        }
+    }
+
+    // what kind of work the instruction does, for tools that profile a program: "alu" computes,
+    // "memory" moves data to or from memory (lea only computes the address), and "control"
+    // changes where execution goes next, traps included
+    pub fn category(self) -> &'static str {
+        match self {
+            Self::Add | Self::And | Self::Not => "alu",
+            Self::Load
+            | Self::LoadIndirect
+            | Self::LoadRegister
+            | Self::LoadEffectiveAddress
+            | Self::Store
+            | Self::StoreIndirect
+            | Self::StoreRegister => "memory",
+            Self::Branch
+            | Self::Jump
+            | Self::JumpSubroutine
+            | Self::JumpSubroutineRegister
+            | Self::NoOperation
+            | Self::Return
+            | Self::ReturnInterrupt
+            | Self::Trap
+            | Self::Getc
+            | Self::Out
+            | Self::Puts
+            | Self::In
+            | Self::Putsp
+            | Self::Halt => "control",
+        }
+    }
+
+    // the number of operands this instruction takes when written with the given mnemonic and
+    // followed by the given tokens, which only differs from num_args for a branch that doesn't
+    // take its condition codes as a separate operand
+    //
+    // the condition codes are only a separate operand when a target follows them, so a lone
+    // operand of a bare br is its target even when it reads like condition codes (a label p)
+    pub fn num_operands(self, mnemonic: &str, operands: &[Token]) -> usize {
+        match self {
+            Self::Branch if mnemonic.eq_ignore_ascii_case("br") && operands.len() >= 2 && parse_condition(operands[0].text).is_some() => 2,
+            Self::Branch => 1,
+            _ => self.num_args(),
+        }
+    }
+}
+
+impl TryFrom<&str> for Instruction {
+
+    type Error = &'static str;
+
+    // mnemonics are matched without regard to case
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        let s = s.to_ascii_lowercase();
+        match s.as_str() {
+            "add" => Ok(Self::Add),
+            "and" => Ok(Self::And),
+            "br" => Ok(Self::Branch),
+            _ if s.strip_prefix("br").and_then(parse_condition).is_some() => Ok(Self::Branch),
+            "jmp" => Ok(Self::Jump),
+            "jsr" => Ok(Self::JumpSubroutine),
+            "jsrr" => Ok(Self::JumpSubroutineRegister),
+            "ld" => Ok(Self::Load),
+            "ldi" => Ok(Self::LoadIndirect),
+            "ldr" => Ok(Self::LoadRegister),
+            "lea" => Ok(Self::LoadEffectiveAddress),
+            "nop" => Ok(Self::NoOperation),
+            "not" => Ok(Self::Not),
+            "ret" => Ok(Self::Return),
+            "rti" => Ok(Self::ReturnInterrupt),
+            "st" => Ok(Self::Store),
+            "sti" => Ok(Self::StoreIndirect),
+            "str" => Ok(Self::StoreRegister),
+            "trap" => Ok(Self::Trap),
+            "getc" => Ok(Self::Getc),
+            "out" => Ok(Self::Out),
+            "puts" => Ok(Self::Puts),
+            "in" => Ok(Self::In),
+            "putsp" => Ok(Self::Putsp),
+            "halt" => Ok(Self::Halt),
+            _ => Err("Invalid instruction"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum InstructionData {
+    Add {
+        dr: u8,
+        sr1: u8,
+        sr2: u8,
+    },
+
+    AddImmediate {
+        dr: u8,
+        sr1: u8,
+        imm5: i8,
+    },
+    
+    And {
+        dr: u8,
+        sr1: u8,
+        sr2: u8,
+    },
+
+    AndImmediate {
+        dr: u8,
+        sr1: u8,
+        imm5: i8,
+    },
+
+    Branch {
+        nzp: u8,
+        pc_offset9: i16,
+    },
+    
+    Jump {
+        base_r: u8,
+    },
+
+    JumpSubroutine {
+        pc_offset11: i16,
+    },
+
+    JumpSubroutineRegister {
+        base_r: u8,
+    },
+
+    Load {
+        dr: u8,
+        pc_offset9: i16,
+    },
+
+    LoadIndirect {
+        dr: u8,
+        pc_offset9: i16,
+    },
+
+    LoadRegister {
+        dr: u8,
+        base_r: u8,
+        offset6: i8,
+    },
+
+    LoadEffectiveAddress {
+        dr: u8,
+        pc_offset9: i16,
+    },
+
+    Not {
+        dr: u8,
+        sr: u8,
+    },
+
+    ReturnInterrupt,
+
+    Store {
+        sr: u8,
+        pc_offset9: i16,
+    },
+
+    StoreIndirect {
+        sr: u8,
+        pc_offset9: i16,
+    },
+
+    StoreRegister {
+        sr: u8,
+        base_r: u8,
+        offset6: i8,
+    },
+
+    Trap {
+        trapvect8: u8,
+    },
+}
+
+impl InstructionData {
+    pub fn binary(self) -> u16 {
+        match self {
+            Self::Add { dr, sr1, sr2 } => (dr as u16) << 9 | (sr1 as u16) << 6 | (sr2 as u16),
+            Self::AddImmediate { dr, sr1, imm5 } => (dr as u16) << 9 | (sr1 as u16) << 6 | 1 << 5 | (imm5 as u16) & ((1 << 5) - 1),
+            Self::And { dr, sr1, sr2 } => (dr as u16) << 9 | (sr1 as u16) << 6 | (sr2 as u16),
+            Self::AndImmediate { dr, sr1, imm5 } => (dr as u16) << 9 | (sr1 as u16) << 6 | 1 << 5 | (imm5 as u16) & ((1 << 5) - 1),
+            Self::Branch { nzp, pc_offset9 } => (nzp as u16) << 9 | (pc_offset9 as u16) & ((1 << 9) - 1),
+            Self::Jump { base_r } => (base_r as u16) << 6,
+            Self::JumpSubroutine { pc_offset11 } => 1 << 11 | pc_offset11 as u16 & ((1 << 11) - 1),
+            Self::JumpSubroutineRegister { base_r } => (base_r as u16) << 6,
+            Self::Load { dr, pc_offset9 } => (dr as u16) << 9 | (pc_offset9 as u16) & ((1 << 9) - 1),
+            Self::LoadIndirect { dr, pc_offset9 } => (dr as u16) << 9 | (pc_offset9 as u16) & ((1 << 9) - 1),
+            Self::LoadRegister { dr, base_r, offset6 } => (dr as u16) << 9 | (base_r as u16) << 6 | (offset6 as u16) & ((1 << 6) - 1),
+            Self::LoadEffectiveAddress { dr, pc_offset9 } => (dr as u16) << 9 | (pc_offset9 as u16) & ((1 << 9) - 1),
+            Self::Not { dr, sr } => (dr as u16) << 9 | (sr as u16) << 6 | 0b111111,
+            Self::ReturnInterrupt => 0b000000000000,
+            Self::Store { sr, pc_offset9 } => (sr as u16) << 9 | (pc_offset9 as u16) & ((1 << 9) - 1),
+            Self::StoreIndirect { sr, pc_offset9 } => (sr as u16) << 9 | (pc_offset9 as u16) & ((1 << 9) - 1),
+            Self::StoreRegister { sr, base_r, offset6 } => (sr as u16) << 9 | (base_r as u16) << 6 | (offset6 as u16) & ((1 << 6) - 1),
+            Self::Trap { trapvect8 } => trapvect8 as u16,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Directive {
+    Orig,
+    End,
+    Fill,
+    Blkw,
+    Stringz,
+    Stringp,
+}
+
+impl Directive {
+    pub const ALL: [Self; 6] = [Self::Orig, Self::End, Self::Fill, Self::Blkw, Self::Stringz, Self::Stringp];
+
+    // the most operands the directive takes
+    pub fn num_args(self) -> usize {
+        match self {
+            Self::Orig => 1,
+            Self::End => 0,
+            Self::Fill => 1,
+            Self::Blkw => 2,
+            Self::Stringz => 1,
+            Self::Stringp => 1,
+        }
+    }
+
+    // what the directive puts in the object file
+    pub fn description(self) -> &'static str {
+        match self {
+            Self::Orig => "the address the program is loaded at, as the first word",
+            Self::End => "nothing, and nothing after it is assembled",
+            Self::Fill => "one word holding a value or the address of a label",
+            Self::Blkw => "count words, each holding the value or 0",
+            Self::Stringz => "one word per character, then a 0",
+            Self::Stringp => "two characters per word, then a 0",
+        }
+    }
+
+    // the number of operands this directive takes when followed by the given tokens, which only
+    // differs from num_args for a .blkw without an initial value
+    pub fn num_operands(self, operands: &[Token]) -> usize {
+        match self {
+            Self::Blkw if operands.len() < 2 => 1,
+            _ => self.num_args(),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Orig => ".ORIG",
+            Self::End => ".END",
+            Self::Fill => ".FILL",
+            Self::Blkw => ".BLKW",
+            Self::Stringz => ".STRINGZ",
+            Self::Stringp => ".STRINGP",
+        }
+    }
+}
+
+impl TryFrom<&str> for Directive {
+
+    type Error = &'static str;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        match s.to_ascii_lowercase().as_str() {
+            ".orig" => Ok(Self::Orig),
+            ".end" => Ok(Self::End),
+            ".fill" => Ok(Self::Fill),
+            ".blkw" => Ok(Self::Blkw),
+            ".stringz" => Ok(Self::Stringz),
+            ".stringp" => Ok(Self::Stringp),
+            _ => Err("Unknown directive"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum DirectiveData {
+    Orig {
+        address: u16,
+    },
+
+    End,
+
+    Fill {
+        value: u16,
+    },
+
+    Blkw {
+        count: u16,
+        value: u16,
+    },
+
+    Stringz {
+        value: String,
+    },
+
+    Stringp {
+        value: String,
+    },
+}
+
+impl DirectiveData {
+    // the origin is a header rather than part of the program, so it takes up no memory
+    pub fn size(&self) -> u16 {
+        match self {
+            Self::Orig { .. } => 0,
+            Self::End => 0,
+            Self::Fill { .. } => 1,
+            Self::Blkw { count, .. } => *count,
+            Self::Stringz { value } => value.chars().count() as u16 + 1,
+            Self::Stringp { value } => value.chars().count().div_ceil(2) as u16 + 1,
+        }
+    }
+
+    pub fn directive(&self) -> Directive {
+        match self {
+            Self::Orig { .. } => Directive::Orig,
+            Self::End => Directive::End,
+            Self::Fill { .. } => Directive::Fill,
+            Self::Blkw { .. } => Directive::Blkw,
+            Self::Stringz { .. } => Directive::Stringz,
+            Self::Stringp { .. } => Directive::Stringp,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Statement {
+    Instruction(Instruction, InstructionData),
+    Directive(DirectiveData),
+}
+
+impl Statement {
+    // the mnemonic or directive the statement was written with, in uppercase
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Instruction(instruction, _) => instruction.name(),
+            Self::Directive(directive_data) => directive_data.directive().name(),
+        }
+    }
+
+    // the category of an instruction, where a directive is always "data"
+    pub fn category(&self) -> &'static str {
+        match self {
+            Self::Instruction(instruction, _) => instruction.category(),
+            Self::Directive(_) => "data",
+        }
+    }
+
+    pub fn words(&self) -> Vec<u16> {
+        match self {
+            Self::Instruction(instruction, instruction_data) => {
+                let word = instruction.binary() << 12 | instruction_data.binary();
+                debug_assert_ne!(word >> 12, 0b1101, "{} encoded with the reserved opcode", instruction.name());
+                vec![word]
+            },
+            Self::Directive(DirectiveData::Orig { address }) => vec![*address],
+            Self::Directive(DirectiveData::End) => vec![],
+            Self::Directive(DirectiveData::Fill { value }) => vec![*value],
+            Self::Directive(DirectiveData::Blkw { count, value }) => vec![*value; *count as usize],
+            Self::Directive(DirectiveData::Stringz { value }) => value.chars().map(|c| c as u16).chain([0]).collect(),
+            // two characters to a word, the first in the low byte, as PUTSP expects
+            Self::Directive(DirectiveData::Stringp { value }) => {
+                let chars = value.chars().map(|c| c as u16).collect::<Vec<_>>();
+                chars.chunks(2).map(|pair| pair[0] | pair.get(1).copied().unwrap_or(0) << 8).chain([0]).collect()
+            },
+        }
+    }
+}
+
+pub type SymbolTable = HashMap<String, u16>;
+
+// constants named with .equ, kept apart from labels since they have a value rather than an address
+pub type ConstantTable = HashMap<String, i32>;
+
+// labels are folded to lowercase unless they are case-sensitive
+fn label_key(label: &str, case_sensitive: bool) -> String {
+    match case_sensitive {
+        true => label.to_string(),
+        false => label.to_ascii_lowercase(),
+    }
+}
+
+// the symbol table as the second pass sees it, where a label is looked up the same way it was defined
+#[derive(Clone, Copy)]
+struct Labels<'a> {
+    symbols: &'a SymbolTable,
+    case_sensitive: bool,
+}
+
+impl Labels<'_> {
+    fn get(&self, label: &str) -> Result<u16, ErrorKind> {
+        if let Some(address) = self.symbols.get(&label_key(label, self.case_sensitive)) {
+            return Ok(*address);
+        }
+
+        // a label that only differs in case is almost certainly the one that was meant
+        match self.symbols.keys().find(|defined| defined.eq_ignore_ascii_case(label)) {
+            Some(defined) => Err(ErrorKind::UndefinedLabel { label: label.into(), defined: Some(defined.clone()) }),
+            None => Err(ErrorKind::UndefinedLabel { label: label.into(), defined: None }),
+        }
+    }
+}
+
+// condition codes are any combination of n, z, and p, in either case
+fn parse_condition(s: &str) -> Option<u8> {
+    if s.is_empty() {
+        return None;
+    }
+
+    s.chars().try_fold(0, |nzp, c| match c.to_ascii_lowercase() {
+        'n' => Some(nzp | 0b100),
+        'z' => Some(nzp | 0b010),
+        'p' => Some(nzp | 0b001),
+        _ => None,
+    })
+}
+
+// the number of anything shaped like a register (r followed by digits), whether or not it exists
+fn register_number(s: &str) -> Option<u32> {
+    let digits = s.strip_prefix(['r', 'R'])?;
+    match !digits.is_empty() && digits.len() <= 3 && digits.chars().all(|c| c.is_ascii_digit()) {
+        true => digits.parse().ok(),
+        false => None,
+    }
+}
+
+// the register a conventional alias stands for, the stack pointer and frame pointer
+fn register_alias(s: &str) -> Option<&'static str> {
+    match s.to_ascii_lowercase().as_str() {
+        "sp" => Some("r6"),
+        "fp" => Some("r5"),
+        _ => None,
+    }
+}
+
+// a register alias is written as the register it stands for, unless aliases are turned off
+fn resolve_alias<'t>(token: Token<'t>, config: Config) -> Token<'t> {
+    match register_alias(token.text) {
+        Some(register) if !config.no_register_aliases => Token { text: register, ..token },
+        _ => token,
+    }
+}
+
+// what an operand that should have been a register was written as, so the error can say
+fn operand_kind(s: &str) -> &'static str {
+    if parse_number::<i32>(s).is_ok_and(|number| number.is_some()) {
+        "immediate "
+    } else if is_label(s) {
+        "label "
+    } else {
+        ""
+    }
+}
+
+fn parse_register(s: &str, instruction: Instruction) -> Result<u8, ErrorKind> {
+    match register_number(s) {
+        Some(register) if register < 8 => Ok(register as u8),
+        Some(register) => Err(ErrorKind::RegisterOutOfRange { register }),
+        None => Err(ErrorKind::BadRegister { instruction, operand: s.into(), kind: operand_kind(s) }),
+    }
+}
+
+// the character following a backslash in a string or character literal
+fn parse_escape(c: char) -> Result<char, ErrorKind> {
+    match c {
+        'n' => Ok('\n'),
+        't' => Ok('\t'),
+        '0' => Ok('\0'),
+        '"' => Ok('"'),
+        '\'' => Ok('\''),
+        '\\' => Ok('\\'),
+        _ => Err(format!("Invalid escape sequence '\\{}'", c).into()),
+    }
+}
+
+fn parse_char(s: &str) -> Result<char, ErrorKind> {
+    let mut chars = s.strip_prefix('\'').ok_or("Expected character literal")?.chars();
+
+    let c = match chars.next() {
+        Some('\'') => return Err("Empty character literal".into()),
+        Some('\\') => parse_escape(chars.next().ok_or("Unterminated character literal")?)?,
+        Some(c) if !c.is_ascii() => return Err(format!("Non-ASCII character '{}' in character literal", c).into()),
+        Some(c) => c,
+        None => return Err("Unterminated character literal".into()),
+    };
+
+    match chars.next() {
+        Some('\'') if chars.next().is_none() => Ok(c),
+        Some(_) => Err(format!("Character literal {} must contain a single character", s).into()),
+        None => Err("Unterminated character literal".into()),
+    }
+}
+
+// numeric literals are hex with an x prefix, binary with a b prefix, decimal with an optional
+// # prefix, where hex and decimal may be negative (and any may be explicitly positive), or a quoted
+// character standing for its ascii code
+//
+// only a malformed character or negative binary literal is an error, anything else that isn't a
+// number is none
+fn parse_number<T: TryFrom<i128>>(s: &str) -> Result<Option<T>, ErrorKind> {
+    if s.starts_with('\'') {
+        return Ok(T::try_from(parse_char(s)? as i128).ok());
+    }
+
+    let Some(prefix) = s.chars().next() else {
+        return Ok(None);
+    };
+
+    let (radix, digits) = match prefix {
+        'x' | 'X' => (16, &s[1..]),
+        'b' | 'B' => (2, &s[1..]),
+        '#' => (10, &s[1..]),
+        _ => (10, s),
+    };
+
+    // a binary literal is a bit pattern, so a negative one is written out in two's complement
+    // (b1111111111111011) rather than with a sign
+    let (negative, digits) = match digits.strip_prefix('-') {
+        Some(_) if radix == 2 => return Err(format!("Binary literal '{}' can't be negative, write it in two's complement", s).into()),
+        Some(digits) => (true, digits),
+        None => (false, digits.strip_prefix('+').unwrap_or(digits)),
+    };
+
+    // num_parse stops at the first non-digit and panics on overflow, so check the digits up front
+    if digits.is_empty() || digits.len() > 16 || !digits.chars().all(|c| c.is_digit(radix)) {
+        return Ok(None);
+    }
+
+    let value = parse_uint_with_radix::<i128>(digits, radix);
+    Ok(value.and_then(|value| T::try_from(if negative { -value } else { value }).ok()))
+}
+
+pub fn parse_address(s: &str) -> Result<u16, ErrorKind> {
+    match parse_number::<i32>(s)? {
+        Some(address @ 0..=0xFFFF) => Ok(address as u16),
+        Some(_) => Err(ErrorKind::AddressOutOfRange { operand: s.into() }),
+        None => Err("Invalid address".into()),
+    }
+}
+
+fn parse_count(s: &str) -> Result<u16, ErrorKind> {
+    match parse_number::<i32>(s)? {
+        Some(count @ 1..=0xFFFF) => Ok(count as u16),
+        _ => Err(ErrorKind::BadCount { operand: Some(s.into()) }),
+    }
+}
+
+fn parse_string(s: &str, directive: Directive) -> Result<String, ErrorKind> {
+    let mut chars = match s.strip_prefix('"') {
+        Some(rest) => rest.chars(),
+        None => return Err(format!("{} expects a string literal, got '{}'", directive.name(), s).into()),
+    };
+    let mut value = String::new();
+
+    loop {
+        match chars.next() {
+            Some('"') => break,
+            Some('\\') => value.push(parse_escape(chars.next().ok_or("Unterminated string literal")?)?),
+            // lc-3 text is ascii, so anything else has no character code to store
+            Some(c) if !c.is_ascii() => return Err(format!("Non-ASCII character '{}' in string literal", c).into()),
+            Some(c) => value.push(c),
+            None => return Err("Unterminated string literal".into()),
+        }
+    }
+
+    if chars.next().is_some() {
+        return Err("Unexpected characters after string literal".into());
+    }
+
+    Ok(value)
+}
+
+// a value encoded into a signed field of the given width, anything outside of it would be
+// silently truncated by the encoder
+fn check_signed(value: i32, bits: u32, field: &str) -> Result<i32, ErrorKind> {
+    let (min, max) = (-(1 << (bits - 1)), (1 << (bits - 1)) - 1);
+    if value < min || value > max {
+        return Err(ErrorKind::ImmediateOutOfRange { field: field.into(), value, bits });
+    }
+
+    Ok(value)
+}
+
+// an offset is either written literally or computed from a label relative to the incremented pc,
+// and either way it has to fit in the signed field it is encoded into
+fn parse_offset(s: &str, bits: u32, instruction: Instruction, labels: Labels, address: u16) -> Result<i16, ErrorKind> {
+    let offset = match parse_number::<i32>(s)? {
+        Some(offset) => return Ok(check_signed(offset, bits, "Offset")? as i16),
+        // a label close to a defined one keeps its hint, anything else could have been either
+        None => match labels.get(s) {
+            Ok(target) => target as i32 - (address as i32 + 1),
+            Err(ErrorKind::UndefinedLabel { defined: None, .. }) => return Err(ErrorKind::BadOffset { operand: s.into() }),
+            Err(e) => return Err(e),
+        },
+    };
+
+    let (min, max) = (-(1 << (bits - 1)), (1 << (bits - 1)) - 1);
+    if offset < min || offset > max {
+        return Err(ErrorKind::OffsetOutOfReach { instruction, label: s.into(), offset, bits });
+    }
+
+    Ok(offset as i16)
+}
+
+// a word may be written signed or unsigned, negative values are stored as two's complement
+fn parse_word(s: &str) -> Result<u16, ErrorKind> {
+    match parse_number::<i32>(s)? {
+        Some(value @ -0x8000..=0xFFFF) => Ok(value as u16),
+        Some(_) => Err(format!("Value does not fit in 16 bits: {} (valid #-32768..xFFFF)", s).into()),
+        None => Err("Invalid value".into()),
+    }
+}
+
+// the last operand of ADD and AND, which picks between the register and immediate encodings
+enum SourceOperand {
+    Register(u8),
+    Immediate(i8),
+}
+
+// anything shaped like a register (r followed by digits) is one, so r8 is a bad register rather
+// than a bad immediate, and anything that is neither a register nor a number is an error rather
+// than a guess at which was meant
+fn parse_source_operand(s: &str, instruction: Instruction) -> Result<SourceOperand, ErrorKind> {
+    if register_number(s).is_some() {
+        return Ok(SourceOperand::Register(parse_register(s, instruction)?));
+    }
+
+    match parse_number::<i32>(s)? {
+        Some(imm5) => Ok(SourceOperand::Immediate(check_signed(imm5, 5, "imm5")? as i8)),
+        None => Err(format!("Expected register (R0-R7) or immediate (#n or xn) in {}, got '{}'", instruction.name(), s).into()),
+    }
+}
+
+fn parse(args: &mut &[Token], labels: Labels, address: u16) -> Result<(Instruction, InstructionData), ErrorKind>
+{
+    if args.is_empty() {
+        return Err("No instruction".into());
+    }
+
+    let mnemonic = args[0].text;
+    let instruction = Instruction::try_from(mnemonic).map_err(|_| ErrorKind::UnknownInstruction { name: mnemonic.into() })?;
+    *args = &args[1..];
+
+    let num_operands = instruction.num_operands(mnemonic, args);
+    if num_operands > args.len() {
+        return Err(ErrorKind::MissingOperand { name: instruction.name(), expected: num_operands, got: args.len() });
+    }
+
+    // each arm gives the number of operands it read along with what it read from them
+    let (instruction_data, read) = match instruction {
+        Instruction::Add => {
+            let dr = parse_register(args[0].text, instruction)?;
+            let sr1 = parse_register(args[1].text, instruction)?;
+
+            match parse_source_operand(args[2].text, instruction)? {
+                SourceOperand::Register(sr2) => (InstructionData::Add { dr, sr1, sr2 }, 3),
+                SourceOperand::Immediate(imm5) => (InstructionData::AddImmediate { dr, sr1, imm5 }, 3),
+            }
+        },
+        Instruction::And => {
+            let dr = parse_register(args[0].text, instruction)?;
+            let sr1 = parse_register(args[1].text, instruction)?;
+
+            match parse_source_operand(args[2].text, instruction)? {
+                SourceOperand::Register(sr2) => (InstructionData::And { dr, sr1, sr2 }, 3),
+                SourceOperand::Immediate(imm5) => (InstructionData::AndImmediate { dr, sr1, imm5 }, 3),
+            }
+        },
+        Instruction::Branch => {
+            // the condition codes are either fused into the mnemonic (brnz) or given as their own
+            // operand (br nz), and a branch without any is unconditional
+            let (nzp, target, read) = if num_operands == 2 {
+                (parse_condition(args[0].text).unwrap_or_default(), args[1].text, 2)
+            } else {
+                (parse_condition(&mnemonic[2..]).unwrap_or(0b111), args[0].text, 1)
+            };
+
+            let pc_offset9 = parse_offset(target, 9, instruction, labels, address)?;
+            (InstructionData::Branch { nzp, pc_offset9 }, read)
+        },
+        Instruction::Jump => {
+            let base_r = parse_register(args[0].text, instruction)?;
+            (InstructionData::Jump { base_r }, 1)
+        },
+        Instruction::JumpSubroutine => {
+            let pc_offset11 = parse_offset(args[0].text, 11, instruction, labels, address)?;
+            (InstructionData::JumpSubroutine { pc_offset11 }, 1)
+        },
+        Instruction::JumpSubroutineRegister => {
+            let base_r = parse_register(args[0].text, instruction)?;
+            (InstructionData::JumpSubroutineRegister { base_r }, 1)
+        },
+        Instruction::Load => {
+            let dr = parse_register(args[0].text, instruction)?;
+            let pc_offset9 = parse_offset(args[1].text, 9, instruction, labels, address)?;
+            (InstructionData::Load { dr, pc_offset9 }, 2)
+        },
+        Instruction::LoadIndirect => {
+            let dr = parse_register(args[0].text, instruction)?;
+            let pc_offset9 = parse_offset(args[1].text, 9, instruction, labels, address)?;
+            (InstructionData::LoadIndirect { dr, pc_offset9 }, 2)
+        },
+        Instruction::LoadRegister => {
+            let dr = parse_register(args[0].text, instruction)?;
+            let base_r = parse_register(args[1].text, instruction)?;
+            let offset6 = parse_number::<i32>(args[2].text)?
+                .ok_or_else(|| format!("Expected offset in {}, got '{}'", instruction.name(), args[2].text))?;
+            let offset6 = check_signed(offset6, 6, &format!("{} offset6", instruction.name()))? as i8;
+            (InstructionData::LoadRegister { dr, base_r, offset6 }, 3)
+        },
+        Instruction::LoadEffectiveAddress => {
+            let dr = parse_register(args[0].text, instruction)?;
+            let pc_offset9 = parse_offset(args[1].text, 9, instruction, labels, address)?;
+            (InstructionData::LoadEffectiveAddress { dr, pc_offset9 }, 2)
+        },
+        // a branch that never branches
+        Instruction::NoOperation => (InstructionData::Branch { nzp: 0, pc_offset9: 0 }, 0),
+        Instruction::Not => {
+            let dr = parse_register(args[0].text, instruction)?;
+            let sr = parse_register(args[1].text, instruction)?;
+            (InstructionData::Not { dr, sr }, 2)
+        },
+        // RET is JMP R7, so it shares its encoding rather than keeping a copy of it
+        Instruction::Return => (InstructionData::Jump { base_r: 7 }, 0),
+        Instruction::ReturnInterrupt => (InstructionData::ReturnInterrupt, 0),
+        Instruction::Store => {
+            let sr = parse_register(args[0].text, instruction)?;
+            let pc_offset9 = parse_offset(args[1].text, 9, instruction, labels, address)?;
+            (InstructionData::Store { sr, pc_offset9 }, 2)
+        },
+        Instruction::StoreIndirect => {
+            let sr = parse_register(args[0].text, instruction)?;
+            let pc_offset9 = parse_offset(args[1].text, 9, instruction, labels, address)?;
+            (InstructionData::StoreIndirect { sr, pc_offset9 }, 2)
+        },
+        Instruction::StoreRegister => {
+            let sr = parse_register(args[0].text, instruction)?;
+            let base_r = parse_register(args[1].text, instruction)?;
+            let offset6 = parse_number::<i32>(args[2].text)?
+                .ok_or_else(|| format!("Expected offset in {}, got '{}'", instruction.name(), args[2].text))?;
+            let offset6 = check_signed(offset6, 6, &format!("{} offset6", instruction.name()))? as i8;
+            (InstructionData::StoreRegister { sr, base_r, offset6 }, 3)
+        },
+        Instruction::Trap => {
+            let trapvect8 = match parse_number::<i32>(args[0].text)? {
+                Some(trapvect8 @ 0..=0xFF) => trapvect8 as u8,
+                Some(_) => return Err(ErrorKind::TrapVectorOutOfRange { operand: args[0].text.into() }),
+                None => return Err(format!("Expected trap vector in {}, got '{}'", instruction.name(), args[0].text).into()),
+            };
+            (InstructionData::Trap { trapvect8 }, 1)
+        },
+        Instruction::Getc | Instruction::Out | Instruction::Puts | Instruction::In | Instruction::Putsp | Instruction::Halt => {
+            (InstructionData::Trap { trapvect8: instruction.trap_vector().unwrap() }, 0)
+        },
+    };
+
+    // the branch arm reads its target from wherever the conditions leave it, so the cursor moves
+    // by what this statement took rather than the most it could have, which has to be what the
+    // first pass set aside for it
+    debug_assert_eq!(read, num_operands, "{} read a different number of operands than it takes", instruction.name());
+    *args = &args[read..];
+    Ok((instruction, instruction_data))
+}
+
+// a .fill either holds a value or the address of a label, optionally moved by a constant like
+// table+3, where the label is only resolved once every label is known (until then it is 0)
+fn parse_fill(s: &str, labels: Option<Labels>) -> Result<u16, ErrorKind> {
+    let sign = s.char_indices().skip(1).find(|&(_, c)| c == '+' || c == '-');
+
+    // a label named x or b followed by an offset would otherwise read as a signed literal, so a
+    // label that is defined (or could be, before every label is known) comes first
+    let labelled = sign.is_some_and(|(i, _)| is_label(&s[..i]) && labels.is_none_or(|labels| labels.get(&s[..i]).is_ok()));
+    if !labelled {
+        if parse_number::<i32>(s)?.is_some() {
+            return parse_word(s);
+        }
+
+        // a trap alias is its vector, for building a trap vector table
+        if let Some(trapvect8) = Instruction::try_from(s).ok().and_then(Instruction::trap_vector) {
+            return Ok(trapvect8 as u16);
+        }
+    }
+
+    let (label, offset) = match sign {
+        Some((i, sign)) => {
+            let offset = parse_number::<i32>(&s[i + 1..])?.ok_or_else(|| format!("Invalid offset in '{}'", s))?;
+            (&s[..i], if sign == '-' { -offset } else { offset })
+        },
+        None => (s, 0),
+    };
+
+    if !is_label(label) {
+        return Err("Invalid value".into());
+    }
+
+    let Some(labels) = labels else {
+        return Ok(0);
+    };
+
+    let address = labels.get(label)? as i32;
+    u16::try_from(address + offset).map_err(|_| format!("'{}' is outside of memory", s).into())
+}
+
+fn parse_directive(args: &mut &[Token], labels: Option<Labels>) -> Result<DirectiveData, ErrorKind> {
+    if args.is_empty() {
+        return Err("No directive".into());
+    }
+
+    let directive = Directive::try_from(args[0].text).map_err(|_| ErrorKind::UnknownDirective { name: args[0].text.into() })?;
+    *args = &args[1..];
+
+    let num_operands = directive.num_operands(args);
+    if num_operands > args.len() {
+        // the block and string directives say what kind of operand they were missing
+        return Err(match directive {
+            Directive::Blkw => ErrorKind::BadCount { operand: None },
+            Directive::Stringz | Directive::Stringp => format!("{} expects a string literal", directive.name()).into(),
+            _ => ErrorKind::MissingOperand { name: directive.name(), expected: num_operands, got: args.len() },
+        });
+    }
+
+    let directive_data = match directive {
+        Directive::Orig => {
+            let address = parse_address(args[0].text)?;
+            DirectiveData::Orig { address }
+        },
+        Directive::End => DirectiveData::End,
+        Directive::Fill => {
+            let value = parse_fill(args[0].text, labels)?;
+            DirectiveData::Fill { value }
+        },
+        // every word of the block starts out as the given value, or zero without one
+        Directive::Blkw => {
+            let count = parse_count(args[0].text)?;
+            let value = match num_operands {
+                2 => parse_word(args[1].text)?,
+                _ => 0,
+            };
+            DirectiveData::Blkw { count, value }
+        },
+        Directive::Stringz => {
+            let value = parse_string(args[0].text, directive)?;
+            DirectiveData::Stringz { value }
+        },
+        Directive::Stringp => {
+            let value = parse_string(args[0].text, directive)?;
+            DirectiveData::Stringp { value }
+        },
+    };
+
+    *args = &args[num_operands..];
+    Ok(directive_data)
+}
+
+// anything starting with a dot is a directive (even one that doesn't exist), so directives are
+// tried before instructions and a misspelled one is never mistaken for an instruction or label
+fn is_directive(token: &Token) -> bool {
+    token.text.starts_with('.')
+}
+
+fn parse_statement(args: &mut &[Token], labels: Labels, address: u16) -> Result<Statement, ErrorKind> {
+    if args.first().is_some_and(is_directive) {
+        Ok(Statement::Directive(parse_directive(args, Some(labels))?))
+    } else {
+        let (instruction, instruction_data) = parse(args, labels, address)?;
+        Ok(Statement::Instruction(instruction, instruction_data))
+    }
+}
+
+// labels start with a letter or underscore, hold only letters, digits, and underscores, and
+// can't be mistaken for a register
+fn is_label(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && register_number(s).is_none()
+}
+
+// what a name that can't be a label would be mistaken for
+fn reserved_name(s: &str) -> Option<&'static str> {
+    if register_number(s).is_some() {
+        Some("a register")
+    } else if s.starts_with('.') {
+        Some("a directive")
+    } else if Instruction::try_from(s).is_ok() {
+        Some("an instruction")
+    } else if parse_number::<i32>(s).is_ok_and(|number| number.is_some()) {
+        // x10 or b1 would be read back as a number wherever it was used
+        Some("a number")
+    } else {
+        None
+    }
+}
+
+fn starts_statement(token: &Token) -> bool {
+    is_directive(token) || Instruction::try_from(token.text).is_ok()
+}
+
+// a statement found by the first pass, left unparsed until every label is known
+struct PendingStatement<'a> {
+    line: usize,
+    address: u16,
+    tokens: &'a [Token<'a>],
+}
+
+// everything the first pass found, along with the errors it found on the way, so the second pass
+// can still look for more
+struct FirstPass<'a> {
+    symbols: SymbolTable,
+    constants: ConstantTable,
+    pending: Vec<PendingStatement<'a>>,
+    warnings: Vec<Warning>,
+    // the location counter once every statement is placed, which is x10000 when the last one
+    // reaches the top of memory
+    end: u32,
+    errors: Vec<AssembleError>,
+}
+
+// places a statement of the given size at the location counter, giving the address that follows it
+fn advance(counter: &mut u32, size: u16, name: &str) -> Result<u16, ErrorKind> {
+    if *counter + size as u32 > 0x10000 {
+        let room = 0x10000 - *counter;
+        return Err(format!("{} at x{:04X} runs past xFFFF (size {}, room for {})", name.to_uppercase(), counter, size, room).into());
+    }
+
+    *counter += size as u32;
+    Ok(*counter as u16)
+}
+
+// the first pass walks the tokens to find where each statement starts and what address it
+// lives at, collecting every label along the way
+//
+// the origin must be the first statement and may only appear once, since every
+// address after it is relative to where the program is loaded
+//
+// anything after .end is dropped without being parsed
+//
+// a raw snippet has no .orig and starts at address 0 instead
+//
+// an origin given in the config takes the place of the file's .orig, or stands in for a missing
+// one, before any label gets its address, while a default origin only stands in for a missing one
+//
+// a constant takes up no memory, so it may be defined anywhere before .end (even before .orig)
+// and is left out of the statements entirely
+fn first_pass<'a>(mut tokens: &'a [Token<'a>], config: Config) -> FirstPass<'a> {
+    let mut errors = Vec::new();
+    let mut symbols = SymbolTable::new();
+    let mut constants = ConstantTable::new();
+    let mut pending = Vec::new();
+    let mut warnings = Vec::new();
+    let mut origin = if config.raw { Some(0) } else { None };
+    let mut address = 0u16;
+    let mut ended = false;
+    // the location counter before it is narrowed to an address, so running off the end of memory
+    // is caught rather than wrapping around and moving every label after it
+    let mut counter = 0u32;
+    // the last label, until a statement that takes up memory comes along for it to name
+    let mut unbound = None;
+    // once a statement runs off the end of memory, so would every one after it
+    let mut full = false;
+
+    while !tokens.is_empty() && !ended && !full {
+        let line = tokens[0].line;
+        // a statement never reaches past the end of its line, so one missing an operand can't
+        // take the first token of the next line in its place
+        let statement = &tokens[..tokens.iter().position(|token| token.line != line).unwrap_or(tokens.len())];
+        let at = |kind: ErrorKind| error_at(line, statement, kind);
+
+        // an error only costs the rest of its line, so the lines after it are still checked
+        let mut place = || -> Result<(), AssembleError> {
+            if statement.iter().take(2).any(is_equ) {
+                if config.strict {
+                    check_strict(statement).map_err(at)?;
+                }
+                let (name, value) = parse_constant(statement).map_err(at)?;
+                if register_alias(name).is_some() && !config.no_register_aliases {
+                    return Err(at(ErrorKind::ReservedLabel { label: name.into(), reason: "a register" }));
+                }
+                let key = label_key(name, config.case_sensitive_labels);
+                if symbols.contains_key(&key) || constants.insert(key, value).is_some() {
+                    return Err(at(ErrorKind::DuplicateLabel { label: name.into() }));
+                }
+                tokens = &tokens[statement.len()..];
+                return Ok(());
+            }
+
+            // a constant defined above is written as its value before the statement is sized, so
+            // the count of a .blkw (or the address of a .orig) can be one
+            let literals = statement
+                .iter()
+                .map(|token| constants.get(&label_key(token.text, config.case_sensitive_labels)).map(|value| format!("#{}", value)))
+                .collect::<Vec<_>>();
+            let resolved = statement
+                .iter()
+                .zip(&literals)
+                .map(|(token, literal)| match literal {
+                    Some(literal) => Token { text: literal, ..*token },
+                    None => *token,
+                })
+                .collect::<Vec<_>>();
+            let mut operands = resolved.as_slice();
+
+            if origin.is_none() && !tokens[0].text.eq_ignore_ascii_case(".orig") {
+                if let Some(origin_address) = config.origin.or(config.default_origin) {
+                    if config.origin.is_none() {
+                        warnings.push(Warning { line: Some(line), message: format!("no .orig, assuming x{:04X}", origin_address) });
+                    }
+                    origin = Some(origin_address);
+                    address = origin_address;
+                    counter = origin_address as u32;
+                }
+            }
+            let statement_address = address;
+
+            // a mnemonic or directive right before another statement was meant as its label, unless
+            // it is a .fill of a trap alias
+            let fills_trap = statement.len() > 1
+                && statement[0].text.eq_ignore_ascii_case(".fill")
+                && Instruction::try_from(statement[1].text).ok().and_then(Instruction::trap_vector).is_some();
+            if statement.len() > 1 && starts_statement(&statement[0]) && starts_statement(&statement[1]) && !fills_trap {
+                let label = statement[0].text.into();
+                return Err(at(ErrorKind::ReservedLabel { label, reason: reserved_name(statement[0].text).unwrap_or_default() }));
+            }
+
+            let name = if is_directive(&tokens[0]) {
+                let directive_data = parse_directive(&mut operands, None).map_err(at)?;
+
+                match directive_data {
+                    DirectiveData::Orig { address: origin_address } => {
+                        if config.raw {
+                            return Err(at(".orig isn't allowed in raw mode".into()));
+                        }
+                        if let Some(origin) = origin {
+                            return Err(at(format!(".orig already set to x{:04X}", origin).into()));
+                        }
+                        let origin_address = match config.origin {
+                            Some(relocated) => {
+                                let message = format!(".orig x{:04X} is overridden by x{:04X}", origin_address, relocated);
+                                warnings.push(Warning { line: Some(line), message });
+                                relocated
+                            },
+                            None => origin_address,
+                        };
+                        // code rarely belongs in the tables and registers the system keeps at either end of memory
+                        let region = match origin_address {
+                            0x0000..=0x00FF => Some("the trap vector table"),
+                            0xFE00..=0xFFFF => Some("the device register region"),
+                            _ => None,
+                        };
+                        if let Some(region) = region {
+                            warnings.push(Warning { line: Some(line), message: format!(".orig x{:04X} is in {}", origin_address, region) });
+                        }
+                        origin = Some(origin_address);
+                        address = origin_address;
+                        counter = origin_address as u32;
+                    },
+                    DirectiveData::End => ended = true,
+                    _ => {
+                        if origin.is_none() {
+                            return Err(at(ErrorKind::StatementBeforeOrig { name: directive_data.directive().name() }));
+                        }
+                        address = advance(&mut counter, directive_data.size(), tokens[0].text).map_err(|kind| {
+                            full = true;
+                            at(kind)
+                        })?;
+                        unbound = None;
+                    },
+                }
+
+                directive_data.directive().name()
+            } else if let Ok(instruction) = Instruction::try_from(tokens[0].text) {
+                if origin.is_none() {
+                    return Err(at(ErrorKind::StatementBeforeOrig { name: instruction.name() }));
+                }
+                let mnemonic = tokens[0].text;
+                operands = &operands[1..];
+                let num_operands = instruction.num_operands(mnemonic, operands);
+                if num_operands > operands.len() {
+                    return Err(at(ErrorKind::MissingOperand { name: instruction.name(), expected: num_operands, got: operands.len() }));
+                }
+                if num_operands == 0 && !operands.is_empty() {
+                    return Err(at(ErrorKind::NoOperands { name: instruction.name() }));
+                }
+                operands = &operands[num_operands..];
+                address = advance(&mut counter, 1, mnemonic).map_err(|kind| {
+                    full = true;
+                    at(kind)
+                })?;
+                unbound = None;
+
+                instruction.name()
+            } else {
+                // a label either stands alone on its line or comes right before the statement it names,
+                // so anything else is a mnemonic that doesn't exist
+                if statement.get(1).is_some_and(|next| !starts_statement(next)) {
+                    return Err(at(ErrorKind::UnknownInstruction { name: tokens[0].text.into() }));
+                }
+                if let Some(reason) = reserved_name(resolve_alias(tokens[0], config).text) {
+                    return Err(at(ErrorKind::ReservedLabel { label: tokens[0].text.into(), reason }));
+                }
+                if !is_label(tokens[0].text) {
+                    return Err(at(ErrorKind::InvalidLabel { label: tokens[0].text.into() }));
+                }
+                if origin.is_none() {
+                    return Err(at("Label before .orig".into()));
+                }
+                let key = label_key(tokens[0].text, config.case_sensitive_labels);
+                if constants.contains_key(&key) || symbols.insert(key, address).is_some() {
+                    return Err(at(ErrorKind::DuplicateLabel { label: tokens[0].text.into() }));
+                }
+                unbound = Some(at(ErrorKind::DanglingLabel { label: tokens[0].text.into() }));
+                tokens = &tokens[1..];
+                return Ok(());
+            };
+
+            if let Some(token) = operands.first() {
+                return Err(at(ErrorKind::UnexpectedOperand { name, operand: token.text.into() }));
+            }
+
+            pending.push(PendingStatement { line, address: statement_address, tokens: statement });
+            tokens = &tokens[statement.len()..];
+            Ok(())
+        };
+
+        // a statement that fails still has the label before it, which would otherwise be
+        // reported as naming nothing on top of the error itself
+        if let Err(error) = place() {
+            errors.push(error);
+            unbound = None;
+            tokens = &tokens[tokens.iter().position(|token| token.line != line).unwrap_or(tokens.len())..];
+        }
+    }
+
+    // a label names the statement after it, even across blank lines and comments, so there has to be one
+    errors.extend(unbound);
+
+    // a file with nothing but whitespace and comments would otherwise be an empty object file
+    if origin.is_none() && !config.raw && errors.is_empty() {
+        errors.push(AssembleError { line: 1, column: None, kind: ErrorKind::EmptyProgram });
+    } else if !ended && !full && !config.raw {
+        warnings.push(Warning { line: None, message: "missing .end".into() });
+    }
+
+    FirstPass { symbols, constants, pending, warnings, end: counter, errors }
+}
+
+fn is_equ(token: &Token) -> bool {
+    token.text.eq_ignore_ascii_case(".equ")
+}
+
+// a constant is defined as either .equ NAME value or NAME .equ value, where the value is any
+// number that fits in a word
+fn parse_constant<'a>(statement: &[Token<'a>]) -> Result<(&'a str, i32), ErrorKind> {
+    let (name, operands) = match statement {
+        [equ, name, operands @ ..] if is_equ(equ) => (name, operands),
+        [name, _, operands @ ..] => (name, operands),
+        _ => return Err(ErrorKind::MissingOperand { name: ".EQU", expected: 2, got: 0 }),
+    };
+
+    if let Some(reason) = reserved_name(name.text) {
+        return Err(ErrorKind::ReservedLabel { label: name.text.into(), reason });
+    }
+    if !is_label(name.text) {
+        return Err(ErrorKind::InvalidLabel { label: name.text.into() });
+    }
+
+    let value = match operands {
+        [] => return Err(ErrorKind::MissingOperand { name: ".EQU", expected: 2, got: 1 }),
+        [value] => value.text,
+        [_, extra, ..] => return Err(ErrorKind::UnexpectedOperand { name: ".EQU", operand: extra.text.into() }),
+    };
+
+    match parse_number::<i32>(value)? {
+        Some(value @ -0x8000..=0xFFFF) => Ok((name.text, value)),
+        Some(_) => Err(format!("Constant out of range: {} (valid -32768..65535)", value).into()),
+        None => Err(format!("Invalid constant '{}'", value).into()),
+    }
+}
+
+// strict mode holds numbers to the prefixed forms, where a bare number would otherwise be decimal
+fn check_strict(tokens: &[Token]) -> Result<(), ErrorKind> {
+    match tokens.iter().find(|token| token.text.starts_with(|c: char| c.is_ascii_digit() || c == '-' || c == '+')) {
+        Some(token) => Err(format!("Number '{}' needs a # or x prefix in strict mode", token.text).into()),
+        None => Ok(()),
+    }
+}
+
+// the second pass parses each statement now that every label is known, keeping the line each
+// statement came from
+//
+// a constant is written in place of its name as a decimal literal, so it can be used wherever a
+// number can and is checked the same way
+//
+// each statement is parsed as it is asked for, so a caller can use it right away rather than
+// holding on to all of them, and any warnings are added to the others as it goes
+fn second_pass<'b>(
+    first_pass: &'b FirstPass,
+    config: Config,
+    warnings: &'b mut Vec<Warning>,
+) -> impl Iterator<Item = Result<(Span, Statement), AssembleError>> + 'b {
+    let FirstPass { symbols, constants, pending, .. } = first_pass;
+    let labels = Labels { symbols, case_sensitive: config.case_sensitive_labels };
+    let literals = constants.iter().map(|(name, value)| (name, format!("#{}", value))).collect::<HashMap<_, _>>();
+    pending
+        .iter()
+        .map(move |&PendingStatement { line, address, tokens }| {
+            let span = Span::of(line, tokens);
+            let tokens = tokens
+                .iter()
+                .map(|token| match literals.get(&label_key(token.text, config.case_sensitive_labels)) {
+                    Some(literal) => Token { text: literal, ..*token },
+                    None => resolve_alias(*token, config),
+                })
+                .collect::<Vec<_>>();
+
+            if config.strict {
+                check_strict(&tokens).map_err(|kind| error_at(line, &tokens, kind))?;
+            }
+
+            // an instruction has at most one immediate, so a truncated one is written in its place
+            // and the statement parsed again
+            let statement = match parse_statement(&mut tokens.as_slice(), labels, address) {
+                Err(ErrorKind::ImmediateOutOfRange { field, value, bits }) if config.warn_truncation => {
+                    let truncated = value << (32 - bits) >> (32 - bits);
+                    let message = format!("{} {} doesn't fit in {} bits and is truncated to {}", field, value, bits, truncated);
+                    warnings.push(Warning { line: Some(line), message });
+                    let literal = format!("#{}", truncated);
+                    let tokens = tokens
+                        .iter()
+                        .map(|token| match parse_number::<i32>(token.text) {
+                            Ok(Some(number)) if number == value => Token { text: &literal, ..*token },
+                            _ => *token,
+                        })
+                        .collect::<Vec<_>>();
+                    parse_statement(&mut tokens.as_slice(), labels, address)
+                },
+                statement => statement,
+            };
+
+            // the lint only looks at unconditional branches, since a conditional one can fall through
+            if let Ok(Statement::Instruction(_, InstructionData::Branch { nzp: 0b111, pc_offset9: -1 })) = &statement {
+                if config.warn_self_loop {
+                    let message = format!("branch at x{:04X} always goes to itself and loops forever", address);
+                    warnings.push(Warning { line: Some(line), message });
+                }
+            }
+
+            statement
+                .map(|statement| (span, statement))
+                .map_err(|kind| error_at(line, &tokens, kind))
+        })
+}
+
+// what is wrong with a statement, where the common mistakes get their own kind so callers can
+// tell them apart and everything else is just a message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorKind {
+    UnknownInstruction {
+        name: String,
+    },
+
+    UnknownDirective {
+        name: String,
+    },
+
+    InvalidLabel {
+        label: String,
+    },
+
+    // a label named like a register, instruction, or directive, which it would be mistaken for
+    ReservedLabel {
+        label: String,
+        reason: &'static str,
+    },
+
+    DuplicateLabel {
+        label: String,
+    },
+
+    // a label with no statement after it before .end
+    DanglingLabel {
+        label: String,
+    },
+
+    // an instruction or data directive before the origin is set, usually a forgotten .orig
+    StatementBeforeOrig {
+        name: &'static str,
+    },
+
+    // defined is a label that only differs in case, when labels are case-sensitive
+    UndefinedLabel {
+        label: String,
+        defined: Option<String>,
+    },
+
+    // neither a defined label nor a number, where a pc-relative offset can be either
+    BadOffset {
+        operand: String,
+    },
+
+    // kind is what the operand looks like instead, an immediate or a label (or nothing)
+    BadRegister {
+        instruction: Instruction,
+        operand: String,
+        kind: &'static str,
+    },
+
+    RegisterOutOfRange {
+        register: u32,
+    },
+
+    TrapVectorOutOfRange {
+        operand: String,
+    },
+
+    AddressOutOfRange {
+        operand: String,
+    },
+
+    // a .blkw count that is missing, zero, or negative
+    BadCount {
+        operand: Option<String>,
+    },
+
+    ImmediateOutOfRange {
+        field: String,
+        value: i32,
+        bits: u32,
+    },
+
+    // a label too far away for the pc-relative field of the instruction referring to it
+    OffsetOutOfReach {
+        instruction: Instruction,
+        label: String,
+        offset: i32,
+        bits: u32,
+    },
+
+    MissingOperand {
+        name: &'static str,
+        expected: usize,
+        got: usize,
+    },
+
+    // an operand given to an instruction that never takes any, like halt or ret
+    NoOperands {
+        name: &'static str,
+    },
+
+    UnexpectedOperand {
+        name: &'static str,
+        operand: String,
+    },
+
+    EmptyOperand,
+
+    // a source file without a single statement, not even .orig
+    EmptyProgram,
+
+    // source is ascii outside of comments, so a stray character is most likely a paste gone wrong
+    UnexpectedCharacter {
+        character: char,
+        column: usize,
+    },
+
+    Message(String),
+}
+
+impl ErrorKind {
+    // the token the error is about as it was written, when the error knows it
+    pub fn token(&self) -> Option<&str> {
+        match self {
+            Self::UnknownInstruction { name } | Self::UnknownDirective { name } => Some(name),
+            Self::InvalidLabel { label }
+            | Self::ReservedLabel { label, .. }
+            | Self::DuplicateLabel { label }
+            | Self::DanglingLabel { label }
+            | Self::UndefinedLabel { label, .. }
+            | Self::OffsetOutOfReach { label, .. } => Some(label),
+            Self::BadOffset { operand } | Self::BadRegister { operand, .. } | Self::UnexpectedOperand { operand, .. } => Some(operand),
+            Self::TrapVectorOutOfRange { operand } | Self::AddressOutOfRange { operand } => Some(operand),
+            Self::BadCount { operand } => operand.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+impl From<String> for ErrorKind {
+    fn from(message: String) -> Self {
+        Self::Message(message)
+    }
+}
+
+impl From<&str> for ErrorKind {
+    fn from(message: &str) -> Self {
+        Self::Message(message.into())
+    }
+}
+
+impl std::fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::UnknownInstruction { name } => write!(f, "Unknown instruction '{}'", name),
+            Self::UnknownDirective { name } => write!(f, "Unknown directive '{}'", name),
+            Self::InvalidLabel { label } => write!(f, "Invalid label '{}'", label),
+            Self::ReservedLabel { label, reason } => write!(f, "'{}' can't be a label since it would be mistaken for {}", label, reason),
+            Self::DuplicateLabel { label } => write!(f, "Duplicate label '{}'", label),
+            Self::DanglingLabel { label } => write!(f, "Label '{}' doesn't name any statement", label),
+            Self::StatementBeforeOrig { name } => write!(f, "Statement before .orig ({})", name),
+            Self::UndefinedLabel { label, defined: Some(defined) } => {
+                write!(f, "Undefined label '{}' (labels are case-sensitive, did you mean '{}'?)", label, defined)
+            },
+            Self::UndefinedLabel { label, defined: None } => write!(f, "Undefined label '{}'", label),
+            Self::BadOffset { operand } => write!(f, "Undefined label or invalid offset '{}'", operand),
+            Self::BadRegister { instruction, operand, kind } => {
+                write!(f, "{} expects a register, got {}'{}'", instruction.name(), kind, operand)
+            },
+            Self::RegisterOutOfRange { register } => write!(f, "Register out of range: R{} (valid R0-R7)", register),
+            Self::TrapVectorOutOfRange { operand } => write!(f, "Trap vector out of range: {} (valid x00..xFF)", operand),
+            Self::AddressOutOfRange { operand } => write!(f, "Address out of range: {} (valid x0000..xFFFF)", operand),
+            Self::BadCount { operand: Some(operand) } => write!(f, ".BLKW expects a positive count, got '{}'", operand),
+            Self::BadCount { operand: None } => write!(f, ".BLKW expects a positive count"),
+            Self::ImmediateOutOfRange { field, value, bits } => {
+                let (min, max) = (-(1 << (bits - 1)), (1 << (bits - 1)) - 1);
+                write!(f, "{} out of range: {} (valid {}..{})", field, value, min, max)
+            },
+            // a label out of reach is a layout problem, so say how far off it is and how to get around it
+            Self::OffsetOutOfReach { instruction, label, offset, bits } => {
+                let (min, max) = (-(1 << (bits - 1)), (1 << (bits - 1)) - 1);
+                let (bound, excess) = match *offset > max {
+                    true => (format!("max {}", max), offset - max),
+                    false => (format!("min {}", min), min - offset),
+                };
+                let workaround = match instruction {
+                    Instruction::Branch => "JMP through a register",
+                    Instruction::JumpSubroutine => "JSRR through a register",
+                    Instruction::LoadEffectiveAddress => "LD of a pointer",
+                    Instruction::Store | Instruction::StoreIndirect => "STI via a pointer",
+                    _ => "LDI via a pointer",
+                };
+                write!(
+                    f,
+                    "{} offset to {} is {} words, exceeds {} by {}; move label closer or use {}",
+                    instruction.name(), label.to_uppercase(), offset, bound, excess, workaround,
+                )
+            },
+            Self::MissingOperand { name, expected, got } => {
+                write!(f, "{} expects {} operand{}, found {}", name, expected, if *expected == 1 { "" } else { "s" }, got)
+            },
+            Self::NoOperands { name } => write!(f, "{} takes no operands", name),
+            Self::UnexpectedOperand { name, operand } => write!(f, "Unexpected '{}' after {}", operand, name),
+            Self::EmptyOperand => write!(f, "Empty operand"),
+            Self::EmptyProgram => write!(f, "Empty program: missing .orig"),
+            Self::UnexpectedCharacter { character, column } => write!(f, "Unexpected character '{}' at column {}", character, column),
+            Self::Message(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+// an error along with the line of the statement it was found in, and the column of the token it
+// is about when it is about one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssembleError {
+    pub line: usize,
+    pub column: Option<usize>,
+    pub kind: ErrorKind,
+}
+
+// an error in a statement, placed at the token of the statement it names (or one starting with
+// it, for a label moved by an offset like table+3)
+fn error_at(line: usize, tokens: &[Token], kind: ErrorKind) -> AssembleError {
+    let column = kind.token().and_then(|text| {
+        tokens
+            .iter()
+            .find(|token| token.text == text)
+            .or_else(|| tokens.iter().find(|token| token.text.starts_with(text)))
+            .map(|token| token.col)
+    });
+    AssembleError { line, column, kind }
+}
+
+impl std::fmt::Display for AssembleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.kind)
+    }
+}
+
+impl std::error::Error for AssembleError {}
+
+// something that assembles but is most likely a mistake, along with the line it is on when it is
+// about a single one, which is left to the caller to report
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl std::fmt::Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "line {}: {}", line, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+// where a statement was written, as its line and the columns its tokens cover, counting characters
+// from 1 up to but not including the end
+//
+// a statement the assembler adds itself, like the origin word of a relocated program or the
+// padding after it, covers no columns of the line it is credited to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Span {
+    pub line: usize,
+    pub columns: std::ops::Range<usize>,
+}
+
+impl Span {
+    fn of(line: usize, tokens: &[Token]) -> Self {
+        let start = tokens.first().map_or(1, |token| token.col);
+        let end = tokens.last().map_or(start, |token| token.col + token.text.chars().count());
+        Self { line, columns: start..end }
+    }
+
+    fn added(line: usize) -> Self {
+        Self { line, columns: 1..1 }
+    }
+
+    pub fn contains(&self, line: usize, column: usize) -> bool {
+        self.line == line && self.columns.contains(&column)
+    }
+}
+
+// an assembled source file, where every statement is paired with where it was written
+#[derive(Debug, Clone)]
+pub struct Program {
+    pub statements: Vec<(Span, Statement)>,
+    pub symbols: SymbolTable,
+    // the location counter once every statement is placed, just past the last word, which is
+    // x10000 for a program that reaches the top of memory rather than wrapping around to 0
+    pub end: u32,
+    pub warnings: Vec<Warning>,
+}
+
+impl Program {
+    pub fn words(&self) -> Vec<u16> {
+        self.statements.iter().flat_map(|(_, statement)| statement.words()).collect()
+    }
+
+    // where the words of a statement start in the program's words, so one that was changed without
+    // moving any label can be encoded again on its own and written over its old words
+    pub fn word_index(&self, statement: usize) -> usize {
+        self.statements[..statement].iter().map(|(_, statement)| statement.words().len()).sum()
+    }
+
+    // the statement written at a line and column, for an editor to find the one an edit is in
+    pub fn statement_at(&self, line: usize, column: usize) -> Option<usize> {
+        self.statements.iter().position(|(span, _)| span.contains(line, column))
+    }
+
+    // where the program is loaded, which is 0 for a raw snippet
+    pub fn origin(&self) -> u16 {
+        self.statements
+            .iter()
+            .find_map(|(_, statement)| match statement {
+                Statement::Directive(DirectiveData::Orig { address }) => Some(*address),
+                _ => None,
+            })
+            .unwrap_or_default()
+    }
+
+    // the address of a label, matched the same way it was when the program was assembled
+    pub fn address_of(&self, label: &str, config: Config) -> Option<u16> {
+        self.symbols.get(&label_key(label, config.case_sensitive_labels)).copied()
+    }
+}
+
+// how a source file is assembled
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Config {
+    // assemble a bare list of statements at address 0, with no .orig and so no origin word, which
+    // means the output is just the program's words and can't be loaded as an object file
+    pub raw: bool,
+
+    // every decimal must be written with # (and hex with x), so a bare 5 is an error
+    pub strict: bool,
+
+    // Loop and loop are different labels rather than the same one
+    pub case_sensitive_labels: bool,
+
+    // load the program here instead of at its .orig, which it doesn't need to have
+    pub origin: Option<u16>,
+
+    // load a program without a .orig here, rather than that being an error
+    pub default_origin: Option<u16>,
+
+    // an immediate too wide for its field is cut down to its low bits with a warning, rather than
+    // being an error
+    pub warn_truncation: bool,
+
+    // fill the rest of memory up to this address with zeros, for simulators that load a whole region
+    pub pad_to: Option<u16>,
+
+    // sp and fp are just names rather than r6 and r5
+    pub no_register_aliases: bool,
+
+    // warn about an unconditional branch to its own address, which is usually a mistake
+    pub warn_self_loop: bool,
+}
+
+// the tokens of a source file along with the errors found reading them, up to the end of the line
+// with .end, since anything after it is dropped without being read (typos and stray characters
+// included)
+fn tokenize(source: &str) -> (Vec<Token<'_>>, Vec<AssembleError>) {
+    let mut tokens = Vec::new();
+    let mut errors = Vec::new();
+    let mut end = None;
+
+    for result in Tokenizer::new(source) {
+        let line = match &result {
+            Ok(token) => token.line,
+            Err(error) => error.line,
+        };
+        if end.is_some_and(|end| line > end) {
+            break;
+        }
+
+        match result {
+            Ok(token) => {
+                if token.text.eq_ignore_ascii_case(".end") {
+                    end = Some(token.line);
+                }
+                tokens.push(token);
+            },
+            Err(error) => errors.push(error),
+        }
+    }
+
+    (tokens, errors)
+}
+
+// assembles a whole source file, carrying on past an error to report every one it can find, in
+// the order each pass found them
+//
+// a line with an error is left out of the rest of the assembly, so it can still cause others, like
+// an undefined label when the line that defines it is the one with the error
+pub fn assemble_reporting_all(source: &str, config: Config) -> Result<Program, Vec<AssembleError>> {
+    let (mut tokens, mut errors) = tokenize(source);
+    tokens.retain(|token| errors.iter().all(|error| error.line != token.line));
+
+    let mut first_pass = first_pass(&tokens, config);
+    errors.append(&mut first_pass.errors);
+    let mut warnings = std::mem::take(&mut first_pass.warnings);
+    // a statement that doesn't parse is left out and its error added to the others
+    let mut statements = second_pass(&first_pass, config, &mut warnings)
+        .filter_map(|result| result.map_err(|error| errors.push(error)).ok())
+        .collect::<Vec<_>>();
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    let FirstPass { symbols, mut end, .. } = first_pass;
+
+    // a relocated program's origin word is the new origin, and one without a .orig of its own
+    // still needs that word, which is credited to its first statement
+    if let (Some(address), Some((span, _))) = (config.origin.or(config.default_origin), statements.first()) {
+        let line = span.line;
+        match &mut statements[0].1 {
+            Statement::Directive(DirectiveData::Orig { address: origin }) => {
+                if config.origin.is_some() {
+                    *origin = address;
+                }
+            },
+            _ => statements.insert(0, (Span::added(line), Statement::Directive(DirectiveData::Orig { address }))),
+        }
+    }
+
+    // the padding is credited to the last statement, like a .blkw written after it
+    if let (Some(pad_to), Some((span, _))) = (config.pad_to, statements.last()) {
+        let line = span.line;
+        if end > pad_to as u32 {
+            let kind = format!("Program ends at x{:04X}, past the padding address x{:04X}", end, pad_to).into();
+            return Err(vec![AssembleError { line, column: None, kind }]);
+        }
+        if end < pad_to as u32 {
+            statements.push((Span::added(line), Statement::Directive(DirectiveData::Blkw { count: (pad_to as u32 - end) as u16, value: 0 })));
+            end = pad_to as u32;
+        }
+    }
+
+    Ok(Program { statements, symbols, end, warnings })
+}
+
+// assembles a whole source file into the same words as assemble_program, handing each one to emit
+// as soon as its statement is parsed instead of keeping the program, for very large sources
+//
+// it stops at the first error, by which point the words before it have already been emitted, and
+// otherwise gives the warnings
+pub fn assemble_streaming(source: &str, config: Config, mut emit: impl FnMut(u16)) -> Result<Vec<Warning>, AssembleError> {
+    let (tokens, errors) = tokenize(source);
+    let mut first_pass = first_pass(&tokens, config);
+    if let Some(error) = errors.first().or(first_pass.errors.first()) {
+        return Err(error.clone());
+    }
+
+    // the end of the program is known before any of it is emitted, so padding that doesn't fit is
+    // an error before the first word rather than after the last
+    if let (Some(pad_to), Some(last)) = (config.pad_to, first_pass.pending.last()) {
+        if first_pass.end > pad_to as u32 {
+            let kind = format!("Program ends at x{:04X}, past the padding address x{:04X}", first_pass.end, pad_to).into();
+            return Err(AssembleError { line: last.line, column: None, kind });
+        }
+    }
+
+    let mut warnings = std::mem::take(&mut first_pass.warnings);
+    let mut last_line = None;
+    for result in second_pass(&first_pass, config, &mut warnings) {
+        let (span, statement) = result?;
+        match statement {
+            Statement::Directive(DirectiveData::Orig { address }) => emit(config.origin.unwrap_or(address)),
+            statement => {
+                // a program without a .orig of its own gets the origin word it was given
+                if let (None, Some(origin)) = (last_line, config.origin.or(config.default_origin)) {
+                    emit(origin);
+                }
+                statement.words().into_iter().for_each(&mut emit);
+            },
+        }
+        last_line = Some(span.line);
+    }
+
+    if let (Some(pad_to), Some(_)) = (config.pad_to, last_line) {
+        (first_pass.end..pad_to as u32).for_each(|_| emit(0));
+    }
+
+    Ok(warnings)
+}
+
+// assembles a whole source file, stopping at the first error
+pub fn assemble_program(source: &str, config: Config) -> Result<Program, AssembleError> {
+    assemble_reporting_all(source, config).map_err(|errors| errors.into_iter().next().unwrap())
+}
+
+// parses a whole source file into its statements and symbol table without keeping the words, for
+// editors that change a statement in place and encode the program again
+pub fn parse_program(source: &str, config: Config) -> Result<Program, AssembleError> {
+    assemble_program(source, config)
+}
+
+// the words of a parsed program's object file, origin first
+pub fn encode(program: &Program) -> Vec<u16> {
+    program.words()
+}
+
+// assembles a whole source file into the words of its object file, origin first
+pub fn assemble(source: &str) -> Result<Vec<u16>, AssembleError> {
+    Ok(assemble_program(source, Config::default())?.words())
+}
+
+// assembles a whole source file as if it were loaded at the given origin, giving the words that
+// follow the origin word along with the symbol table
+//
+// the source's own .orig is ignored, and unless it is required a source without one is fine too
+pub fn assemble_with_origin(source: &str, origin: u16, require_orig: bool) -> Result<(Vec<u16>, SymbolTable), AssembleError> {
+    if require_orig {
+        let (tokens, _) = tokenize(source);
+        if !tokens.iter().any(|token| token.text.eq_ignore_ascii_case(".orig")) {
+            let line = tokens.first().map_or(1, |token| token.line);
+            return Err(AssembleError { line, column: None, kind: "Missing .orig".into() });
+        }
+    }
+
+    let program = assemble_program(source, Config { origin: Some(origin), ..Config::default() })?;
+    Ok((program.words()[1..].to_vec(), program.symbols))
+}
+
+// assembles a whole source file into the address and value of every word it loads, leaving out
+// the origin word, where each statement is only encoded once the iterator gets to it
+pub fn assemble_iter(source: &str) -> Result<impl Iterator<Item = (u16, u16)>, AssembleError> {
+    let program = assemble_program(source, Config::default())?;
+    let mut address = 0u16;
+
+    Ok(program.statements.into_iter().flat_map(move |(_, statement)| {
+        let words = match statement {
+            Statement::Directive(DirectiveData::Orig { address: origin }) => {
+                address = origin;
+                Vec::new()
+            },
+            statement => statement.words(),
+        };
+
+        let start = address;
+        address = address.wrapping_add(words.len() as u16);
+        words.into_iter().enumerate().map(move |(i, word)| (start.wrapping_add(i as u16), word))
+    }))
+}
+
+// encodes a single instruction as if it were at the given address, for tools that show the
+// machine code as it is typed, where labels are looked up in lowercase like the symbol table of
+// a program assembled with the default config
+pub fn assemble_line(source: &str, address: u16, symbols: &SymbolTable) -> Result<u16, AssembleError> {
+    let tokens = Tokenizer::new(source)
+        .map(|token| token.map(|token| resolve_alias(token, Config::default())))
+        .collect::<Result<Vec<_>, _>>()?;
+    let at = |kind: ErrorKind| error_at(1, &tokens, kind);
+    let mut args = tokens.as_slice();
+
+    let (instruction, instruction_data) = parse(&mut args, Labels { symbols, case_sensitive: false }, address).map_err(at)?;
+    if let Some(token) = args.first() {
+        return Err(at(match instruction.num_args() {
+            0 => ErrorKind::NoOperands { name: instruction.name() },
+            _ => ErrorKind::UnexpectedOperand { name: instruction.name(), operand: token.text.into() },
+        }));
+    }
+
+    Ok(instruction.binary() << 12 | instruction_data.binary())
+}
+
+// a token along with where it starts, where the column counts characters from 1
+#[derive(Debug, Clone, Copy)]
+pub struct Token<'a> {
+    pub text: &'a str,
+    pub line: usize,
+    pub col: usize,
+}
+
+pub struct Tokenizer<'a> {
+    input: &'a str,
+    pos: usize,
+    line: usize,
+    line_start: usize,
+    // whether the line has had a token yet, and whether a comma came after the last one
+    started: bool,
+    separated: bool,
+}
+
+impl<'a> Tokenizer<'a> {
+    pub fn new(input: &'a str) -> Self {
+        Self { input, pos: 0, line: 1, line_start: 0, started: false, separated: false }
+    }
+
+    // a comma with nothing before it, nothing after it, or another comma right after it leaves
+    // an operand out, which is almost always a typo
+    fn empty_operand(&mut self) -> Option<Result<Token<'a>, AssembleError>> {
+        self.separated = false;
+        Some(Err(AssembleError { line: self.line, column: None, kind: ErrorKind::EmptyOperand }))
+    }
+}
+
+impl<'a> Iterator for Tokenizer<'a> {
+    type Item = Result<Token<'a>, AssembleError>;
+
+    // a quoted region (single or double) is kept as part of a single token (spaces and commas
+    // included) up to the closing quote or the end of the line, whichever comes first
+    //
+    // an unquoted semicolon or double slash starts a comment that runs to the end of the line
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut count = 0;
+        let mut quote = None;
+        let mut escaped = false;
+        let mut comment = false;
+
+        for c in self.input[self.pos..].chars() {
+            if c == '\n' && count == 0 {
+                if self.separated {
+                    return self.empty_operand();
+                }
+                self.line += 1;
+                self.line_start = self.pos + 1;
+                self.started = false;
+            }
+
+            if comment {
+                comment = c != '\n';
+                self.pos += c.len_utf8();
+            } else if let Some(q) = quote {
+                if c == '\n' {
+                    break;
+                }
+
+                if escaped {
+                    escaped = false;
+                } else if c == '\\' {
+                    escaped = true;
+                } else if c == q {
+                    quote = None;
+                }
+
+                count += c.len_utf8();
+            } else if c == ';' || self.input[self.pos + count..].starts_with("//") {
+                if count > 0 {
+                    break;
+                }
+
+                comment = true;
+                self.pos += c.len_utf8();
+            } else if c.is_whitespace() || c == ',' {
+                if count > 0 {
+                    break;
+                }
+
+                if c == ',' {
+                    if self.separated || !self.started {
+                        self.pos += c.len_utf8();
+                        return self.empty_operand();
+                    }
+                    self.separated = true;
+                }
+
+                self.pos += c.len_utf8();
+            } else if !c.is_ascii() {
+                let column = self.input[self.line_start..self.pos + count].chars().count() + 1;
+                self.pos += count + c.len_utf8();
+                // the character stands in for an operand, so a comma before it isn't left dangling
+                self.started = true;
+                self.separated = false;
+                let kind = ErrorKind::UnexpectedCharacter { character: c, column };
+                return Some(Err(AssembleError { line: self.line, column: Some(column), kind }));
+            } else {
+                if c == '"' || c == '\'' {
+                    quote = Some(c);
+                }
+                count += c.len_utf8();
+            }
+        }
+
+        if count > 0 {
+            let col = self.input[self.line_start..self.pos].chars().count() + 1;
+            let token = Token { text: &self.input[self.pos..self.pos + count], line: self.line, col };
+            self.pos += count;
+            self.started = true;
+            self.separated = false;
+            Some(Ok(token))
+        } else if self.separated {
+            self.empty_operand()
+        } else {
+            None
+        }
+    }
+}
+
+fn sign_extend(value: u16, bits: u32) -> i16 {
+    ((value << (16 - bits)) as i16) >> (16 - bits)
+}
+
+// the inverse of the encoders, which only recognizes a word if encoding what was decoded gives
+// back the exact same word (so unused bits that are set make it data rather than an instruction)
+pub fn decode(word: u16) -> Option<(Instruction, InstructionData)> {
+    let dr = (word >> 9 & 0b111) as u8;
+    let sr1 = (word >> 6 & 0b111) as u8;
+    let sr2 = (word & 0b111) as u8;
+    let imm5 = sign_extend(word, 5) as i8;
+    let offset6 = sign_extend(word, 6) as i8;
+    let pc_offset9 = sign_extend(word, 9);
+    let immediate = word >> 5 & 1 == 1;
+
+    let (instruction, instruction_data) = match word >> 12 {
+        0b0001 if immediate => (Instruction::Add, InstructionData::AddImmediate { dr, sr1, imm5 }),
+        0b0001 => (Instruction::Add, InstructionData::Add { dr, sr1, sr2 }),
+        0b0101 if immediate => (Instruction::And, InstructionData::AndImmediate { dr, sr1, imm5 }),
+        0b0101 => (Instruction::And, InstructionData::And { dr, sr1, sr2 }),
+        0b0000 if word == 0 => (Instruction::NoOperation, InstructionData::Branch { nzp: 0, pc_offset9: 0 }),
+        // a branch without condition codes would be written as an unconditional one, so it is data
+        0b0000 if dr == 0 => return None,
+        0b0000 => (Instruction::Branch, InstructionData::Branch { nzp: dr, pc_offset9 }),
+        0b1100 if sr1 == 7 => (Instruction::Return, InstructionData::Jump { base_r: sr1 }),
+        0b1100 => (Instruction::Jump, InstructionData::Jump { base_r: sr1 }),
+        0b0100 if word >> 11 & 1 == 1 => (Instruction::JumpSubroutine, InstructionData::JumpSubroutine { pc_offset11: sign_extend(word, 11) }),
+        0b0100 => (Instruction::JumpSubroutineRegister, InstructionData::JumpSubroutineRegister { base_r: sr1 }),
+        0b0010 => (Instruction::Load, InstructionData::Load { dr, pc_offset9 }),
+        0b1010 => (Instruction::LoadIndirect, InstructionData::LoadIndirect { dr, pc_offset9 }),
+        0b0110 => (Instruction::LoadRegister, InstructionData::LoadRegister { dr, base_r: sr1, offset6 }),
+        0b1110 => (Instruction::LoadEffectiveAddress, InstructionData::LoadEffectiveAddress { dr, pc_offset9 }),
+        0b1001 => (Instruction::Not, InstructionData::Not { dr, sr: sr1 }),
+        0b1000 => (Instruction::ReturnInterrupt, InstructionData::ReturnInterrupt),
+        0b0011 => (Instruction::Store, InstructionData::Store { sr: dr, pc_offset9 }),
+        0b1011 => (Instruction::StoreIndirect, InstructionData::StoreIndirect { sr: dr, pc_offset9 }),
+        0b0111 => (Instruction::StoreRegister, InstructionData::StoreRegister { sr: dr, base_r: sr1, offset6 }),
+        0b1111 => {
+            let trapvect8 = word as u8;
+            let instruction = match trapvect8 {
+                0x20 => Instruction::Getc,
+                0x21 => Instruction::Out,
+                0x22 => Instruction::Puts,
+                0x23 => Instruction::In,
+                0x24 => Instruction::Putsp,
+                0x25 => Instruction::Halt,
+                _ => Instruction::Trap,
+            };
+            (instruction, InstructionData::Trap { trapvect8 })
+        },
+        _ => return None,
+    };
+
+    if instruction.binary() << 12 | instruction_data.binary() != word {
+        return None;
+    }
+
+    Some((instruction, instruction_data))
+}
+
+// formats a decoded instruction the way it would be written in source
+fn format_instruction(instruction: Instruction, instruction_data: InstructionData) -> String {
+    let name = instruction.name();
+
+    match (instruction, instruction_data) {
+        (Instruction::Branch, InstructionData::Branch { nzp, pc_offset9 }) => {
+            let mut condition = String::new();
+            for (bit, c) in [(0b100, 'n'), (0b010, 'z'), (0b001, 'p')] {
+                if nzp & bit != 0 {
+                    condition.push(c);
+                }
+            }
+            format!("{}{} #{}", name, condition, pc_offset9)
+        },
+        (
+            Instruction::NoOperation
+            | Instruction::Return
+            | Instruction::ReturnInterrupt
+            | Instruction::Getc
+            | Instruction::Out
+            | Instruction::Puts
+            | Instruction::In
+            | Instruction::Putsp
+            | Instruction::Halt,
+            _,
+        ) => name.to_string(),
+        (_, InstructionData::Add { dr, sr1, sr2 } | InstructionData::And { dr, sr1, sr2 }) => format!("{} R{}, R{}, R{}", name, dr, sr1, sr2),
+        (_, InstructionData::AddImmediate { dr, sr1, imm5 } | InstructionData::AndImmediate { dr, sr1, imm5 }) => format!("{} R{}, R{}, #{}", name, dr, sr1, imm5),
+        (_, InstructionData::Branch { .. }) => name.to_string(),
+        (_, InstructionData::Jump { base_r } | InstructionData::JumpSubroutineRegister { base_r }) => format!("{} R{}", name, base_r),
+        (_, InstructionData::JumpSubroutine { pc_offset11 }) => format!("{} #{}", name, pc_offset11),
+        (
+            _,
+            InstructionData::Load { dr, pc_offset9 }
+            | InstructionData::LoadIndirect { dr, pc_offset9 }
+            | InstructionData::LoadEffectiveAddress { dr, pc_offset9 }
+            | InstructionData::Store { sr: dr, pc_offset9 }
+            | InstructionData::StoreIndirect { sr: dr, pc_offset9 },
+        ) => format!("{} R{}, #{}", name, dr, pc_offset9),
+        (_, InstructionData::LoadRegister { dr, base_r, offset6 } | InstructionData::StoreRegister { sr: dr, base_r, offset6 }) => {
+            format!("{} R{}, R{}, #{}", name, dr, base_r, offset6)
+        },
+        (_, InstructionData::Not { dr, sr }) => format!("{} R{}, R{}", name, dr, sr),
+        (_, InstructionData::ReturnInterrupt) => name.to_string(),
+        (_, InstructionData::Trap { trapvect8 }) => format!("{} x{:02X}", name, trapvect8),
+    }
+}
+
+// turns the words of an object file (origin first) back into source, with any word that isn't
+// an instruction written out as data, along with warnings about words that look corrupt
+pub fn disassemble(words: &[u16]) -> (Vec<String>, Vec<Warning>) {
+    let Some((origin, words)) = words.split_first() else {
+        return (Vec::new(), Vec::new());
+    };
+
+    let mut warnings = Vec::new();
+    let mut lines = vec![format!(".ORIG x{:04X}", origin)];
+
+    for (i, &word) in words.iter().enumerate() {
+        // the opcode no instruction uses is most likely data, but could be a corrupt object file
+        if word >> 12 == 0b1101 {
+            let message = format!("x{:04X}: x{:04X} uses the reserved opcode 1101", origin.wrapping_add(i as u16), word);
+            warnings.push(Warning { line: None, message });
+        }
+
+        lines.push(match decode(word) {
+            Some((instruction, instruction_data)) => format_instruction(instruction, instruction_data),
+            None => format!(".FILL x{:04X}", word),
+        });
+    }
+
+    lines.push(".END".into());
+    (lines, warnings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(source: &str) -> Vec<u16> {
+        assemble(source).unwrap()
+    }
+
+    fn error(source: &str) -> String {
+        assemble(source).unwrap_err().to_string()
+    }
+
+    // the whole word an instruction is encoded as, opcode included
+    fn word(instruction: Instruction, instruction_data: InstructionData) -> u16 {
+        instruction.binary() << 12 | instruction_data.binary()
+    }
+
+    #[test]
+    fn encodes_add_and_and() {
+        assert_eq!(word(Instruction::Add, InstructionData::Add { dr: 7, sr1: 7, sr2: 7 }), 0x1FC7);
+        assert_eq!(word(Instruction::Add, InstructionData::Add { dr: 0, sr1: 0, sr2: 0 }), 0x1000);
+        assert_eq!(word(Instruction::Add, InstructionData::AddImmediate { dr: 0, sr1: 0, imm5: -16 }), 0x1030);
+        assert_eq!(word(Instruction::Add, InstructionData::AddImmediate { dr: 7, sr1: 1, imm5: 15 }), 0x1E6F);
+        assert_eq!(word(Instruction::Add, InstructionData::AddImmediate { dr: 2, sr1: 3, imm5: 0 }), 0x14E0);
+        assert_eq!(word(Instruction::And, InstructionData::And { dr: 7, sr1: 0, sr2: 7 }), 0x5E07);
+        assert_eq!(word(Instruction::And, InstructionData::AndImmediate { dr: 7, sr1: 7, imm5: 0 }), 0x5FE0);
+        assert_eq!(word(Instruction::And, InstructionData::AndImmediate { dr: 0, sr1: 0, imm5: -1 }), 0x503F);
+    }
+
+    #[test]
+    fn encodes_control_flow() {
+        assert_eq!(word(Instruction::Branch, InstructionData::Branch { nzp: 0b111, pc_offset9: -256 }), 0x0F00);
+        assert_eq!(word(Instruction::Branch, InstructionData::Branch { nzp: 0b001, pc_offset9: 255 }), 0x02FF);
+        assert_eq!(word(Instruction::Branch, InstructionData::Branch { nzp: 0b010, pc_offset9: 0 }), 0x0400);
+        assert_eq!(word(Instruction::Jump, InstructionData::Jump { base_r: 7 }), 0xC1C0);
+        assert_eq!(word(Instruction::Jump, InstructionData::Jump { base_r: 0 }), 0xC000);
+        assert_eq!(word(Instruction::JumpSubroutine, InstructionData::JumpSubroutine { pc_offset11: -1024 }), 0x4C00);
+        assert_eq!(word(Instruction::JumpSubroutine, InstructionData::JumpSubroutine { pc_offset11: 1023 }), 0x4BFF);
+        assert_eq!(word(Instruction::JumpSubroutine, InstructionData::JumpSubroutine { pc_offset11: 0 }), 0x4800);
+        assert_eq!(word(Instruction::JumpSubroutineRegister, InstructionData::JumpSubroutineRegister { base_r: 7 }), 0x41C0);
+        assert_eq!(word(Instruction::ReturnInterrupt, InstructionData::ReturnInterrupt), 0x8000);
+        assert_eq!(word(Instruction::Trap, InstructionData::Trap { trapvect8: 0x25 }), 0xF025);
+        assert_eq!(word(Instruction::Trap, InstructionData::Trap { trapvect8: 0xFF }), 0xF0FF);
+        assert_eq!(word(Instruction::Trap, InstructionData::Trap { trapvect8: 0 }), 0xF000);
+    }
+
+    #[test]
+    fn encodes_loads_and_stores() {
+        assert_eq!(word(Instruction::Load, InstructionData::Load { dr: 7, pc_offset9: -256 }), 0x2F00);
+        assert_eq!(word(Instruction::Load, InstructionData::Load { dr: 0, pc_offset9: 0 }), 0x2000);
+        assert_eq!(word(Instruction::LoadIndirect, InstructionData::LoadIndirect { dr: 0, pc_offset9: -1 }), 0xA1FF);
+        assert_eq!(word(Instruction::LoadIndirect, InstructionData::LoadIndirect { dr: 7, pc_offset9: 255 }), 0xAEFF);
+        assert_eq!(word(Instruction::LoadRegister, InstructionData::LoadRegister { dr: 7, base_r: 7, offset6: -32 }), 0x6FE0);
+        assert_eq!(word(Instruction::LoadRegister, InstructionData::LoadRegister { dr: 0, base_r: 0, offset6: 31 }), 0x601F);
+        assert_eq!(word(Instruction::LoadEffectiveAddress, InstructionData::LoadEffectiveAddress { dr: 3, pc_offset9: 0 }), 0xE600);
+        assert_eq!(word(Instruction::LoadEffectiveAddress, InstructionData::LoadEffectiveAddress { dr: 7, pc_offset9: -256 }), 0xEF00);
+        assert_eq!(word(Instruction::Not, InstructionData::Not { dr: 7, sr: 0 }), 0x9E3F);
+        assert_eq!(word(Instruction::Not, InstructionData::Not { dr: 0, sr: 7 }), 0x91FF);
+        assert_eq!(word(Instruction::Store, InstructionData::Store { sr: 7, pc_offset9: -256 }), 0x3F00);
+        assert_eq!(word(Instruction::StoreIndirect, InstructionData::StoreIndirect { sr: 0, pc_offset9: 255 }), 0xB0FF);
+        assert_eq!(word(Instruction::StoreIndirect, InstructionData::StoreIndirect { sr: 7, pc_offset9: 0 }), 0xBE00);
+        assert_eq!(word(Instruction::StoreRegister, InstructionData::StoreRegister { sr: 7, base_r: 6, offset6: -32 }), 0x7FA0);
+        assert_eq!(word(Instruction::StoreRegister, InstructionData::StoreRegister { sr: 0, base_r: 0, offset6: 0 }), 0x7000);
+    }
+
+    #[test]
+    fn label_after_blkw_follows_the_reserved_words() {
+        let program = parse_program(".orig x3000\n.blkw 3\nAFTER .fill AFTER\n.end\n", Config::default()).unwrap();
+        assert_eq!(program.address_of("AFTER", Config::default()), Some(0x3003));
+        assert_eq!(words(".orig x3000\n.blkw 3\nAFTER .fill AFTER\n.end\n"), [0x3000, 0, 0, 0, 0x3003]);
+    }
+
+    #[test]
+    fn branches_forward_and_backward_to_a_label() {
+        let source = ".orig x3000\nBR LOOP\nLOOP ADD R0, R0, #-1\nBRp LOOP\n.end\n";
+        assert_eq!(words(source), [0x3000, 0x0E00, 0x103F, 0x03FE]);
+    }
+
+    #[test]
+    fn halt_is_trap_x25() {
+        assert_eq!(words(".orig x3000\nHALT\n.end\n"), [0x3000, 0xF025]);
+    }
+
+    #[test]
+    fn imm5_takes_minus_16_through_15() {
+        assert_eq!(words(".orig x3000\nADD R0, R0, #-16\nAND R0, R0, #15\n.end\n"), [0x3000, 0x1030, 0x502F]);
+        assert_eq!(error(".orig x3000\nADD R0, R0, #-17\n.end\n"), "line 2: imm5 out of range: -17 (valid -16..15)");
+        assert_eq!(error(".orig x3000\nAND R0, R0, #16\n.end\n"), "line 2: imm5 out of range: 16 (valid -16..15)");
+    }
+
+    #[test]
+    fn offset6_takes_up_to_31() {
+        assert_eq!(words(".orig x3000\nLDR R0 R1 #31\n.end\n"), [0x3000, 0x605F]);
+        assert_eq!(error(".orig x3000\nLDR R0 R1 #32\n.end\n"), "line 2: LDR offset6 out of range: 32 (valid -32..31)");
+    }
+
+    #[test]
+    fn ldi_takes_a_negative_offset_like_ld() {
+        assert_eq!(words(".orig x3000\nLDI R0 #-5\nLD R0 #-5\n.end\n"), [0x3000, 0xA1FB, 0x21FB]);
+    }
+
+    #[test]
+    fn ret_is_jmp_r7() {
+        assert_eq!(words(".orig x3000\nRET\nJMP R7\n.end\n"), [0x3000, 0xC1C0, 0xC1C0]);
+    }
+
+    #[test]
+    fn nop_is_all_zeros() {
+        assert_eq!(words(".orig x3000\nNOP\n.end\n"), [0x3000, 0x0000]);
+    }
+
+    #[test]
+    fn bare_br_is_unconditional() {
+        let source = ".orig x3000\nLOOP BR LOOP\nBRnz LOOP\nP BR P\nBR P\nBR z LOOP\n.end\n";
+        assert_eq!(words(source), [0x3000, 0x0FFF, 0x0DFE, 0x0FFF, 0x0FFE, 0x05FB]);
+    }
+
+    #[test]
+    fn labels_on_the_same_line_or_their_own() {
+        let source = ".orig x3000\nLOOP ADD R0, R0, #-1\nBRp LOOP\nLD R1, DATA\nHALT\nDATA\n.fill x42\n.end\n";
+        let program = parse_program(source, Config::default()).unwrap();
+        assert_eq!(program.address_of("LOOP", Config::default()), Some(0x3000));
+        assert_eq!(program.address_of("DATA", Config::default()), Some(0x3004));
+        assert_eq!(words(source), [0x3000, 0x103F, 0x03FE, 0x2201, 0xF025, 0x0042]);
+    }
+
+    #[test]
+    fn offset_overflow_says_by_how_much() {
+        assert_eq!(
+            error(".orig x3000\nLD R0, FAR\n.blkw 300\nFAR .fill 0\n.end\n"),
+            "line 2: LD offset to FAR is 300 words, exceeds max 255 by 45; move label closer or use LDI via a pointer",
+        );
+        assert_eq!(
+            error(".orig x3000\nJSR FAR\n.blkw 1100\nFAR RET\n.end\n"),
+            "line 2: JSR offset to FAR is 1100 words, exceeds max 1023 by 77; move label closer or use JSRR through a register",
+        );
+    }
+
+    #[test]
+    fn strict_mode_needs_a_prefix_on_decimals() {
+        let strict = Config { strict: true, ..Config::default() };
+        let error = assemble_program(".orig x3000\nADD R0 R1 5\n.end\n", strict).unwrap_err();
+        assert_eq!(error.to_string(), "line 2: Number '5' needs a # or x prefix in strict mode");
+        assert_eq!(assemble_program(".orig x3000\nADD R0 R1 #5\n.end\n", strict).unwrap().words(), [0x3000, 0x1065]);
+        assert_eq!(words(".orig x3000\nADD R0 R1 5\n.end\n"), [0x3000, 0x1065]);
+    }
+
+    #[test]
+    fn empty_operands_are_errors() {
+        assert_eq!(words(".orig x3000\nADD\tR0 ,R1,  R2\nADD R0 R1 R2\n.end\n"), [0x3000, 0x1042, 0x1042]);
+        assert_eq!(error(".orig x3000\nADD R0,,R2\n.end\n"), "line 2: Empty operand");
+        assert_eq!(error(".orig x3000\nADD R0, R1 ,, R2\n.end\n"), "line 2: Empty operand");
+        assert_eq!(error(".orig x3000\nADD R0,R1,R2,\n.end\n"), "line 2: Empty operand");
+        assert_eq!(error(".orig x3000\n,ADD R0,R1,R2\n.end\n"), "line 2: Empty operand");
+    }
+
+    #[test]
+    fn nothing_after_end_is_read() {
+        assert_eq!(words(".orig x3000\nHALT\n.end\nnotes: a, , b\n"), [0x3000, 0xF025]);
+    }
+
+    #[test]
+    fn third_add_operand_is_a_register_or_an_immediate() {
+        assert_eq!(words(".orig x3000\nADD R0 R1 R2\nADD R0 R1 #3\n.end\n"), [0x3000, 0x1042, 0x1063]);
+        assert_eq!(
+            error(".orig x3000\nADD R0 R1 foo\n.end\n"),
+            "line 2: Expected register (R0-R7) or immediate (#n or xn) in ADD, got 'foo'",
+        );
+    }
+
+    #[test]
+    fn rti_is_opcode_1000() {
+        assert_eq!(words(".orig x3000\nRTI\n.end\n"), [0x3000, 0x8000]);
+    }
+
+    #[test]
+    fn binary_decimal_and_hex_literals_agree() {
+        let source = ".orig x3000\nADD R0 R0 b101\nADD R0 R0 #5\nADD R0 R0 x5\n.fill b101\n.fill #5\n.fill x5\n.end\n";
+        assert_eq!(words(source), [0x3000, 0x1025, 0x1025, 0x1025, 0x0005, 0x0005, 0x0005]);
+    }
+
+    #[test]
+    fn origin_must_fit_in_16_bits() {
+        assert_eq!(words(".orig x3000\n.end\n"), [0x3000]);
+        let error = assemble(".orig x10000\n.end\n").unwrap_err();
+        assert_eq!(error.kind, ErrorKind::AddressOutOfRange { operand: "x10000".into() });
+        assert_eq!(error.to_string(), "line 1: Address out of range: x10000 (valid x0000..xFFFF)");
+    }
+
+    #[test]
+    fn program_past_the_top_of_memory_is_an_error() {
+        assert_eq!(words(".orig xFFFE\n.fill 1\n.fill 2\n.end\n"), [0xFFFE, 1, 2]);
+        assert_eq!(
+            error(".orig xFFFE\n.fill 1\n.fill 2\n.fill 3\n.end\n"),
+            "line 4: .FILL at x10000 runs past xFFFF (size 1, room for 0)",
+        );
+    }
+
+    #[test]
+    fn truncated_add_does_not_take_the_next_line() {
+        assert_eq!(error(".orig x3000\nADD R0, R1\nADD R2, R3, R4\n.end\n"), "line 2: ADD expects 3 operands, found 2");
+    }
+
+    #[test]
+    fn consecutive_branches_stay_aligned() {
+        let source = ".orig x3000\nLOOP BRz LOOP\nBR LOOP\nBRnp #1\nBR n LOOP\nHALT\n.end\n";
+        assert_eq!(words(source), [0x3000, 0x05FF, 0x0FFE, 0x0A01, 0x09FC, 0xF025]);
+    }
+
+    #[test]
+    fn strings_keep_their_case() {
+        assert_eq!(words(".ORIG x3000\n.STRINGZ \"Hello\"\n.END\n"), [0x3000, 0x48, 0x65, 0x6C, 0x6C, 0x6F, 0]);
+    }
+
+    #[test]
+    fn labels_fold_case_unless_asked_not_to() {
+        let source = ".orig x3000\nLoop BR loop\n.end\n";
+        assert_eq!(words(source), [0x3000, 0x0FFF]);
+
+        let sensitive = Config { case_sensitive_labels: true, ..Config::default() };
+        assert_eq!(
+            assemble_program(source, sensitive).unwrap_err().to_string(),
+            "line 2: Undefined label 'loop' (labels are case-sensitive, did you mean 'Loop'?)",
+        );
+        let program = assemble_program(".orig x3000\nLoop BR loop\nloop BR Loop\n.end\n", sensitive).unwrap();
+        assert_eq!(program.words(), [0x3000, 0x0E00, 0x0FFE]);
+        assert_eq!(program.address_of("loop", sensitive), Some(0x3001));
+        assert_eq!(program.address_of("LOOP", sensitive), None);
+    }
+
+    #[test]
+    fn blkw_fills_with_zero_or_the_given_value() {
+        assert_eq!(words(".orig x3000\n.blkw 3\n.blkw 2, x5\n.end\n"), [0x3000, 0, 0, 0, 5, 5]);
+    }
+
+    #[test]
+    fn stringp_packs_the_first_character_low() {
+        assert_eq!(words(".orig x3000\n.stringp \"ABCD\"\n.end\n"), [0x3000, 0x4241, 0x4443, 0]);
+        assert_eq!(words(".orig x3000\n.stringp \"ABC\"\n.end\n"), [0x3000, 0x4241, 0x0043, 0]);
+    }
+
+    #[test]
+    fn non_ascii_is_an_error_rather_than_a_panic() {
+        let error = assemble(".orig x3000\nADD R0 é\n.end\n").unwrap_err();
+        assert_eq!(error.to_string(), "line 2: Unexpected character 'é' at column 8");
+        assert_eq!(error.column, Some(8));
+        assert_eq!(words(".orig x3000\nHALT\n.end\ncafé\n"), [0x3000, 0xF025]);
+    }
+
+    #[test]
+    fn labels_that_read_as_something_else_are_rejected() {
+        assert_eq!(
+            error(".orig x3000\nADD .fill 0\n.end\n"),
+            "line 2: 'ADD' can't be a label since it would be mistaken for an instruction",
+        );
+        assert_eq!(error(".orig x3000\nR3 .fill 0\n.end\n"), "line 2: 'R3' can't be a label since it would be mistaken for a register");
+        assert_eq!(
+            error(".orig x3000\n.fill .fill 0\n.end\n"),
+            "line 2: '.fill' can't be a label since it would be mistaken for a directive",
+        );
+        assert_eq!(error(".orig x3000\nX10 .fill 0\n.end\n"), "line 2: 'X10' can't be a label since it would be mistaken for a number");
+        assert_eq!(error(".orig x3000\nB1 .fill 0\n.end\n"), "line 2: 'B1' can't be a label since it would be mistaken for a number");
+    }
+
+    #[test]
+    fn signed_offsets_read_the_same_with_or_without_a_prefix() {
+        let expected = words(".orig x3000\nBR #-3\nBR #3\n.end\n");
+        assert_eq!(expected, [0x3000, 0x0FFD, 0x0E03]);
+        assert_eq!(words(".orig x3000\nBR -3\nBR +3\n.end\n"), expected);
+    }
+
+    #[test]
+    fn nothing_but_comments_comes_before_orig() {
+        assert_eq!(error("ADD R0 R0 R0\n.orig x3000\n.end\n"), "line 1: Statement before .orig (ADD)");
+        assert_eq!(words("; a comment\n\n.orig x3000\nHALT\n.end\n"), [0x3000, 0xF025]);
+    }
+
+    #[test]
+    fn instructions_without_operands_reject_extra_ones() {
+        assert_eq!(words(".orig x3000\nHALT\nRET\nRTI\n.end\n"), [0x3000, 0xF025, 0xC1C0, 0x8000]);
+        assert_eq!(error(".orig x3000\nHALT R0\n.end\n"), "line 2: HALT takes no operands");
+        assert_eq!(error(".orig x3000\nRET R0\n.end\n"), "line 2: RET takes no operands");
+        assert_eq!(error(".orig x3000\nRTI R0\n.end\n"), "line 2: RTI takes no operands");
+    }
+
+    #[test]
+    fn both_comment_styles_outside_strings() {
+        let source = ".orig x3000 // c style\nHALT ; lc-3 style\n.stringz \"a//b\"\n.end\n";
+        assert_eq!(words(source), [0x3000, 0xF025, 0x61, 0x2F, 0x2F, 0x62, 0]);
+    }
+
+    #[test]
+    fn constants_stand_in_for_numbers() {
+        let source = ".orig x3000\nMAX .equ #10\n.equ N 3\nADD R0, R0, MAX\n.fill MAX\n.blkw N\nADD R0, R0, LATER\nLATER .equ 2\n.end\n";
+        assert_eq!(words(source), [0x3000, 0x102A, 0x000A, 0, 0, 0, 0x1022]);
+        assert_eq!(error(".orig x3000\nMAX .equ #10\nMAX .equ #11\n.end\n"), "line 3: Duplicate label 'MAX'");
+    }
+
+    #[test]
+    fn register_operands_reject_immediates() {
+        assert_eq!(error(".orig x3000\nNOT R0 #3\n.end\n"), "line 2: NOT expects a register, got immediate '#3'");
+        assert_eq!(error(".orig x3000\nJMP #5\n.end\n"), "line 2: JMP expects a register, got immediate '#5'");
+    }
+
+    #[test]
+    fn fill_takes_trap_names() {
+        assert_eq!(words(".orig x3000\n.fill HALT\n.fill GETC\n.end\n"), [0x3000, 0x0025, 0x0020]);
+    }
+
+    #[test]
+    fn every_bad_line_is_reported() {
+        let errors = assemble_reporting_all(".orig x3000\nHALT R0\nADD R0\nJMP #1\nHALT\n.end\n", Config::default()).unwrap_err();
+        assert_eq!(errors.iter().map(|error| error.line).collect::<Vec<_>>(), [2, 3, 4]);
+    }
+
+    #[test]
+    fn assembles_a_single_line() {
+        let symbols = SymbolTable::from([("loop".to_string(), 0x3000)]);
+        assert_eq!(assemble_line("ADD R0, R1, #2", 0x3000, &symbols).unwrap(), 0x1062);
+        assert_eq!(assemble_line("BRnz LOOP", 0x3005, &symbols).unwrap(), 0x0DFA);
+        assert_eq!(assemble_line("HALT R0", 0x3000, &symbols).unwrap_err().to_string(), "line 1: HALT takes no operands");
+    }
+
+    #[test]
+    fn immediates_may_be_hex() {
+        assert_eq!(words(".orig x3000\nADD R0, R1, x0F\nAND R0, R1, #-16\n.end\n"), [0x3000, 0x106F, 0x5070]);
+        assert_eq!(error(".orig x3000\nADD R0, R1, x20\n.end\n"), "line 2: imm5 out of range: 32 (valid -16..15)");
+    }
+
+    #[test]
+    fn trap_vectors_fit_in_8_bits() {
+        assert_eq!(words(".orig x3000\nTRAP x25\nTRAP #37\n.end\n"), [0x3000, 0xF025, 0xF025]);
+        let error = assemble(".orig x3000\nTRAP x100\n.end\n").unwrap_err();
+        assert_eq!(error.kind, ErrorKind::TrapVectorOutOfRange { operand: "x100".into() });
+        assert_eq!(error.to_string(), "line 2: Trap vector out of range: x100 (valid x00..xFF)");
+        assert_eq!(error.column, Some(6));
+    }
+
+    #[test]
+    fn empty_programs_need_an_orig() {
+        assert_eq!(error(""), "line 1: Empty program: missing .orig");
+        assert_eq!(error("; just a comment\n\n  // and another\n"), "line 1: Empty program: missing .orig");
+    }
+
+    #[test]
+    fn two_labels_may_share_an_address() {
+        let program = parse_program(".orig x3000\nSTART\nMAIN\nHALT\n.fill MAIN\n.end\n", Config::default()).unwrap();
+        assert_eq!(program.address_of("START", Config::default()), Some(0x3000));
+        assert_eq!(program.address_of("MAIN", Config::default()), Some(0x3000));
+        assert_eq!(program.words(), [0x3000, 0xF025, 0x3000]);
+        assert_eq!(error(".orig x3000\nMAIN HALT\nMAIN HALT\n.end\n"), "line 3: Duplicate label 'MAIN'");
+    }
+
+    #[test]
+    fn origin_moves_absolute_values_but_not_offsets() {
+        let source = ".orig x5000\nLOOP BR LOOP\n.fill LOOP\n.end\n";
+        let (at_3000, symbols) = assemble_with_origin(source, 0x3000, false).unwrap();
+        let (at_4000, _) = assemble_with_origin(source, 0x4000, true).unwrap();
+        assert_eq!(at_3000, [0x0FFF, 0x3000]);
+        assert_eq!(at_4000, [0x0FFF, 0x4000]);
+        assert_eq!(symbols.get("loop"), Some(&0x3000));
+
+        let headerless = "LOOP BR LOOP\n.fill LOOP\n.end\n";
+        assert_eq!(assemble_with_origin(headerless, 0x3000, false).unwrap().0, [0x0FFF, 0x3000]);
+        assert_eq!(assemble_with_origin(headerless, 0x3000, true).unwrap_err().to_string(), "line 1: Missing .orig");
+    }
+
+    #[test]
+    fn no_instruction_uses_the_reserved_opcode() {
+        assert!(Instruction::ALL.iter().all(|instruction| instruction.binary() != 0b1101));
+
+        let (lines, warnings) = disassemble(&[0x3000, 0xD123]);
+        assert_eq!(lines, [".ORIG x3000", ".FILL xD123", ".END"]);
+        assert_eq!(warnings, [Warning { line: None, message: "x3000: xD123 uses the reserved opcode 1101".into() }]);
+    }
+
+    #[test]
+    fn operand_count_errors_say_what_was_expected() {
+        assert_eq!(error(".orig x3000\nADD R0, R1\n.end\n"), "line 2: ADD expects 3 operands, found 2");
+        assert_eq!(error(".orig x3000\nLDR R0\n.end\n"), "line 2: LDR expects 3 operands, found 1");
+    }
+
+    #[test]
+    fn truncation_is_a_warning_when_asked_for() {
+        let source = ".orig x3000\nADD R0,R1,#32\n.end\n";
+        assert_eq!(error(source), "line 2: imm5 out of range: 32 (valid -16..15)");
+
+        let program = assemble_program(source, Config { warn_truncation: true, ..Config::default() }).unwrap();
+        assert_eq!(program.words(), [0x3000, 0x1060]);
+        assert_eq!(program.warnings.iter().map(Warning::to_string).collect::<Vec<_>>(), ["line 2: imm5 32 doesn't fit in 5 bits and is truncated to 0"]);
+    }
+
+    #[test]
+    fn pads_with_zeros_up_to_the_address() {
+        let source = ".orig x3000\nHALT\n.fill 1\n.fill 2\n.end\n";
+        let padded = Config { pad_to: Some(0x3008), ..Config::default() };
+        assert_eq!(assemble_program(source, padded).unwrap().words(), [0x3000, 0xF025, 1, 2, 0, 0, 0, 0, 0]);
+
+        let mut streamed = Vec::new();
+        assemble_streaming(source, padded, |word| streamed.push(word)).unwrap();
+        assert_eq!(streamed, [0x3000, 0xF025, 1, 2, 0, 0, 0, 0, 0]);
+
+        let past_the_top = Config { pad_to: Some(0xFFFF), ..Config::default() };
+        let error = assemble_program(".orig xFFFE\n.fill 1\n.fill 2\n.end\n", past_the_top).unwrap_err();
+        assert_eq!(error.to_string(), "line 4: Program ends at x10000, past the padding address xFFFF");
+        assert!(assemble_streaming(".orig xFFFE\n.fill 1\n.fill 2\n.end\n", past_the_top, |_| {}).is_err());
+    }
+
+    #[test]
+    fn label_alone_binds_to_the_next_statement() {
+        let source = ".orig x3000\nHALT\nLOOP\n\n; comment\nADD R0 R0 #1\nBR LOOP\n.end\n";
+        assert_eq!(parse_program(source, Config::default()).unwrap().address_of("LOOP", Config::default()), Some(0x3001));
+        assert_eq!(words(source), [0x3000, 0xF025, 0x1021, 0x0FFE]);
+        assert_eq!(error(".orig x3000\nHALT\nLOOP\n.end\n"), "line 3: Label 'LOOP' doesn't name any statement");
+
+        let errors = assemble_reporting_all(".orig x3000\nLOOP ADD R0\n.end\n", Config::default()).unwrap_err();
+        assert_eq!(errors.iter().map(ToString::to_string).collect::<Vec<_>>(), ["line 2: ADD expects 3 operands, found 1"]);
+        let errors = assemble_reporting_all(".orig x3000\nLOOP\nADD R0\n.end\n", Config::default()).unwrap_err();
+        assert_eq!(errors.iter().map(ToString::to_string).collect::<Vec<_>>(), ["line 3: ADD expects 3 operands, found 1"]);
+    }
+
+    #[test]
+    fn pc_offsets_are_labels_or_numbers() {
+        assert_eq!(words(".orig x3000\nLD R0, DATA\nLD R0, #3\nHALT\nDATA .fill 0\n.end\n"), [0x3000, 0x2002, 0x2003, 0xF025, 0]);
+        assert_eq!(error(".orig x3000\nLD R0 foo\n.end\n"), "line 2: Undefined label or invalid offset 'foo'");
+    }
+
+    #[test]
+    fn sp_and_fp_are_r6_and_r5() {
+        let source = ".orig x3000\nADD SP, SP, #-1\nLDR R0, FP, #0\n.end\n";
+        assert_eq!(words(source), [0x3000, 0x1DBF, 0x6140]);
+        let error = assemble_program(source, Config { no_register_aliases: true, ..Config::default() }).unwrap_err();
+        assert_eq!(error.to_string(), "line 2: ADD expects a register, got label 'SP'");
+    }
+
+    #[test]
+    fn directive_operand_errors_are_precise() {
+        assert_eq!(error(".orig x3000\n.blkw\n.end\n"), "line 2: .BLKW expects a positive count");
+        assert_eq!(error(".orig x3000\n.blkw #0\n.end\n"), "line 2: .BLKW expects a positive count, got '#0'");
+        assert_eq!(assemble(".orig x3000\n.blkw #-1\n.end\n").unwrap_err().kind, ErrorKind::BadCount { operand: Some("#-1".into()) });
+        assert_eq!(error(".orig x3000\n.stringz hello\n.end\n"), "line 2: .STRINGZ expects a string literal, got 'hello'");
+    }
+
+    #[test]
+    fn fill_takes_signed_and_unsigned_16_bit_values() {
+        assert_eq!(words(".orig x3000\n.fill #-1\n.fill #-32768\n.fill x7FFF\n.fill xFFFF\n.end\n"), [0x3000, 0xFFFF, 0x8000, 0x7FFF, 0xFFFF]);
+        assert_eq!(error(".orig x3000\n.fill #-32769\n.end\n"), "line 2: Value does not fit in 16 bits: #-32769 (valid #-32768..xFFFF)");
+        assert_eq!(error(".orig x3000\n.fill x10000\n.end\n"), "line 2: Value does not fit in 16 bits: x10000 (valid #-32768..xFFFF)");
+    }
+
+    #[test]
+    fn iterator_gives_the_same_words_as_assemble() {
+        let source = ".orig x3000\nLOOP BR LOOP\n.stringz \"ab\"\n.blkw 2, x7\n.fill LOOP\n.end\n";
+        let pairs = assemble_iter(source).unwrap().collect::<Vec<_>>();
+        assert_eq!(pairs.iter().map(|&(_, word)| word).collect::<Vec<_>>(), words(source)[1..]);
+        assert_eq!(pairs.iter().map(|&(address, _)| address).collect::<Vec<_>>(), (0x3000..0x3007).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn data_only_programs_assemble() {
+        assert_eq!(words(".orig x4000\n.fill x1\n.fill #-2\n.fill xBEEF\n.end\n"), [0x4000, 0x0001, 0xFFFE, 0xBEEF]);
+    }
+
+    #[test]
+    fn unconditional_branch_to_itself_warns_when_asked() {
+        let source = ".orig x3000\nHERE BR HERE\nTHERE BRz THERE\n.end\n";
+        assert!(parse_program(source, Config::default()).unwrap().warnings.is_empty());
+
+        let program = assemble_program(source, Config { warn_self_loop: true, ..Config::default() }).unwrap();
+        assert_eq!(program.words(), [0x3000, 0x0FFF, 0x05FF]);
+        assert_eq!(
+            program.warnings,
+            [Warning { line: Some(2), message: "branch at x3000 always goes to itself and loops forever".into() }],
+        );
+    }
+
+    #[test]
+    fn one_statement_can_be_re_encoded() {
+        let mut program = parse_program(".orig x3000\nLOOP ADD R0, R0, #1\nBR LOOP\n.end\n", Config::default()).unwrap();
+        assert_eq!(encode(&program), [0x3000, 0x1021, 0x0FFE]);
+
+        // an edit to the immediate is inside the span of the ADD, which leaves out its label
+        let index = program.statement_at(2, 19).unwrap();
+        assert_eq!(program.statement_at(2, 2), None);
+        let (span, statement) = &mut program.statements[index];
+        assert_eq!(*span, Span { line: 2, columns: 6..20 });
+        let Statement::Instruction(_, InstructionData::AddImmediate { imm5, .. }) = statement else {
+            panic!("expected ADD with an immediate, got {:?}", statement);
+        };
+        *imm5 = -2;
+        assert_eq!(statement.words(), [0x103E]);
+        assert_eq!(encode(&program), [0x3000, 0x103E, 0x0FFE]);
+
+        let raw = parse_program("Loop ADD R0, R0, #1\nBR Loop\n", Config { raw: true, case_sensitive_labels: true, ..Config::default() }).unwrap();
+        assert_eq!(encode(&raw), [0x1021, 0x0FFE]);
+        assert_eq!(raw.statements[1].0, Span { line: 2, columns: 1..8 });
+    }
+
+    #[test]
+    fn fill_holds_a_label_moved_by_a_constant() {
+        let source = ".orig x3000\nTABLE .fill FIRST\n.fill FIRST+1\n.fill THIRD-1\nFIRST HALT\nHALT\nTHIRD HALT\n.end\n";
+        assert_eq!(words(source), [0x3000, 0x3003, 0x3004, 0x3004, 0xF025, 0xF025, 0xF025]);
+
+        // a single letter label isn't read as a radix prefix with a signed number after it
+        let source = ".orig x3000\nX .fill X-1\n.fill X+1\nB .fill B-1\n.fill B+1\n.end\n";
+        assert_eq!(words(source), [0x3000, 0x2FFF, 0x3001, 0x3001, 0x3003]);
+        assert_eq!(words(".orig x3000\n.fill x-1\n.end\n"), [0x3000, 0xFFFF]);
+        assert_eq!(error(".orig x3000\n.fill b-1\n.end\n"), "line 2: Binary literal 'b-1' can't be negative, write it in two's complement");
+    }
+
+    #[test]
+    fn every_instruction_reads_the_operands_it_takes() {
+        // parse checks in debug builds that each arm read as many operands as it takes
+        let source = ".orig x3000\nL ADD R0 R0 R0\nAND R0 R0 #1\nBR L\nBRz L\nBR z L\nJMP R1\nJSR L\nJSRR R1\nLD R0 L\n\
+            LDI R0 L\nLDR R0 R1 #1\nLEA R0 L\nNOP\nNOT R0 R1\nRET\nRTI\nST R0 L\nSTI R0 L\nSTR R0 R1 #1\nTRAP x25\n\
+            GETC\nOUT\nPUTS\nIN\nPUTSP\nHALT\n.end\n";
+        assert_eq!(words(source).len(), 27);
+    }
+}
@@ -6,404 +6,874 @@
 // robust or feature-complete, but rather a simple tool to help people translate valid
 // LC-3 assembly into machine code.
 
-use num_parse::*;
+use std::io::{IsTerminal, Read, Write};
+
+use lc3_assembler::*;
 
 #[derive(Debug, Clone, Copy)]
-enum Instruction {
-    Add,
-    And,
-    Branch,
-    Jump,
-    JumpSubroutine,
-    JumpSubroutineRegister,
-    Load,
-    LoadIndirect,
-    LoadRegister,
-    LoadEffectiveAddress,
-    Not,
-    Return,
-    ReturnInterrupt,
-    Store,
-    StoreIndirect,
-    StoreRegister,
-    Trap,
-}
-
-impl Instruction {
-    fn binary(self) -> u16 {
-        match self {
-            Self::Add => 0b0001,
-            Self::And => 0b0101,
-            Self::Branch => 0b0000,
-            Self::Jump => 0b1100,
-            Self::JumpSubroutine => 0b0100,
-            Self::JumpSubroutineRegister => 0b0100,
-            Self::Load => 0b0010,
-            Self::LoadIndirect => 0b0010,
-            Self::LoadRegister => 0b0110,
-            Self::LoadEffectiveAddress => 0b1110,
-            Self::Not => 0b1001,
-            Self::Return => 0b1100,
-            Self::ReturnInterrupt => 0b1100,
-            Self::Store => 0b0011,
-            Self::StoreIndirect => 0b0011,
-            Self::StoreRegister => 0b0111,
-            Self::Trap => 0b1111,
+enum Format {
+    Bits,
+    Hex,
+    Bin,
+    Json,
+    Meta,
+    Dump,
+    CArray,
+    Mem,
+    Ihex,
+}
+
+impl TryFrom<&str> for Format {
+
+    type Error = &'static str;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        match s {
+            "bits" => Ok(Self::Bits),
+            "hex" => Ok(Self::Hex),
+            "bin" => Ok(Self::Bin),
+            "json" => Ok(Self::Json),
+            "meta" => Ok(Self::Meta),
+            "dump" => Ok(Self::Dump),
+            "c" => Ok(Self::CArray),
+            "mem" => Ok(Self::Mem),
+            "ihex" => Ok(Self::Ihex),
+            _ => Err("Invalid format"),
         }
     }
+}
 
-    // this means that any instructions that share the same keyword must have the same arity
-    fn num_args(self) -> usize {
+impl Format {
+    // the extension of the file the output is written to when it doesn't go to stdout
+    fn extension(self) -> &'static str {
         match self {
-            Self::Add => 3,
-            Self::And => 3,
-            Self::Branch => 2,
-            Self::Jump => 1,
-            Self::JumpSubroutine => 1,
-            Self::JumpSubroutineRegister => 1,
-            Self::Load => 2,
-            Self::LoadIndirect => 2,
-            Self::LoadRegister => 3,
-            Self::LoadEffectiveAddress => 2,
-            Self::Not => 2,
-            Self::Return => 0,
-            Self::ReturnInterrupt => 0,
-            Self::Store => 2,
-            Self::StoreIndirect => 2,
-            Self::StoreRegister => 3,
-            Self::Trap => 1,
+            Self::Bits => "bits",
+            Self::Hex => "hex",
+            Self::Bin => "obj",
+            Self::Json => "json",
+            Self::Meta => "tsv",
+            Self::Dump => "dump",
+            Self::CArray => "c",
+            Self::Mem => "mem",
+            Self::Ihex => "hex",
         }
     }
 }
 
-impl TryFrom<&str> for Instruction {
+// an object file is every word of the program (origin first) written big-endian
+fn object_bytes(words: &[u16]) -> Vec<u8> {
+    words.iter().flat_map(|word| word.to_be_bytes()).collect()
+}
 
-    type Error = &'static str;
+// the sum of every word of the object file, origin included, wrapped to 16 bits
+fn checksum(words: &[u16]) -> u16 {
+    words.iter().fold(0, |sum, word| sum.wrapping_add(*word))
+}
 
-    fn try_from(s: &str) -> Result<Self, Self::Error> {
-        match s {
-            "add" => Ok(Self::Add),
-            "and" => Ok(Self::And),
-            "br" => Ok(Self::Branch),
-            "jmp" => Ok(Self::Jump),
-            "jsr" => Ok(Self::JumpSubroutine),
-            "jsrr" => Ok(Self::JumpSubroutineRegister),
-            "ld" => Ok(Self::Load),
-            "ldi" => Ok(Self::LoadIndirect),
-            "ldr" => Ok(Self::LoadRegister),
-            "lea" => Ok(Self::LoadEffectiveAddress),
-            "not" => Ok(Self::Not),
-            "ret" => Ok(Self::Return),
-            "rti" => Ok(Self::ReturnInterrupt),
-            "st" => Ok(Self::Store),
-            "sti" => Ok(Self::StoreIndirect),
-            "str" => Ok(Self::StoreRegister),
-            "trap" => Ok(Self::Trap),
-            _ => Err("Invalid instruction"),
+fn object_words(bytes: &[u8]) -> Result<Vec<u16>, String> {
+    if bytes.is_empty() || !bytes.len().is_multiple_of(2) {
+        return Err("object file must hold a whole number of words, origin first".into());
+    }
+
+    Ok(bytes.chunks(2).map(|pair| u16::from_be_bytes([pair[0], pair[1]])).collect())
+}
+
+// reads the whole input, from stdin when no path was given
+fn read_input(path: Option<&str>) -> std::io::Result<Vec<u8>> {
+    match path {
+        Some(path) => std::fs::read(path),
+        None => {
+            let mut bytes = Vec::new();
+            std::io::stdin().read_to_end(&mut bytes)?;
+            Ok(bytes)
+        },
+    }
+}
+
+// source text as saved by any editor, without the byte order mark and carriage returns that
+// windows editors tend to leave in
+fn normalize_source(source: &str) -> String {
+    source.strip_prefix('\u{feff}').unwrap_or(source).replace("\r\n", "\n")
+}
+
+struct Options {
+    format: Format,
+    disasm: bool,
+    stop_on_error: bool,
+    // only report errors and write nothing, for editors and hooks
+    check: bool,
+    // write each word as soon as it is assembled
+    stream: bool,
+    // the most errors reported for an input, so a badly broken one doesn't bury the first
+    max_errors: usize,
+    sym: bool,
+    lst: bool,
+    verbose: bool,
+    comments: bool,
+    checksum: bool,
+    // the label execution starts at, rather than the origin
+    entry: Option<String>,
+    // the most words a program may occupy past its origin, for assignments with a memory budget
+    max_words: Option<u32>,
+    // what the array is called in c output
+    c_array: String,
+    // whether a memory init file starts with an @ marker for the origin
+    mem_address: bool,
+    // whether bits and hex lines start with the address of their word
+    with_address: bool,
+    config: Config,
+}
+
+// the output of assembling a source file in the given format, where every word of a text format
+// is followed by the line it came from as it was written (unless comments are left out)
+fn render(source: &str, program: &Program, options: &Options, entry: u16) -> Vec<u8> {
+    match options.format {
+        Format::Bin => return object_bytes(&program.words()),
+        Format::Json => return render_json(source, program, entry, options.config).into_bytes(),
+        Format::Meta => return render_meta(program).into_bytes(),
+        Format::Dump => return render_dump(program).into_bytes(),
+        Format::CArray => return render_c_array(program, &options.c_array).into_bytes(),
+        Format::Mem => return render_mem(program, options.mem_address).into_bytes(),
+        Format::Ihex => return render_ihex(program).into_bytes(),
+        Format::Bits | Format::Hex => {},
+    }
+
+    let lines = source.lines().collect::<Vec<_>>();
+    let mut output = String::new();
+    let mut address = 0u16;
+
+    for (span, statement) in &program.statements {
+        let orig = match statement {
+            Statement::Directive(DirectiveData::Orig { address: start }) => Some(*start),
+            _ => None,
+        };
+
+        for word in statement.words() {
+            // the origin word isn't loaded anywhere, so it gets a blank column instead of an address
+            if options.with_address {
+                output += &match orig {
+                    Some(_) => " ".repeat(7),
+                    None => format!("x{:04X}: ", address),
+                };
+            }
+
+            output += &match options.format {
+                Format::Hex => format!("0x{:04X}", word),
+                _ => format!("{:016b}", word),
+            };
+
+            if options.comments && !span.columns.is_empty() {
+                output += &format!(" // {}", lines[span.line - 1]);
+            }
+
+            output += "\n";
+            if orig.is_none() {
+                address = address.wrapping_add(1);
+            }
+        }
+
+        if let Some(start) = orig {
+            address = start;
         }
     }
+
+    output.into_bytes()
 }
 
-#[derive(Debug, Clone, Copy)]
-enum InstructionData {
-    Add {
-        dr: u8,
-        sr1: u8,
-        sr2: u8,
-    },
-
-    AddImmediate {
-        dr: u8,
-        sr1: u8,
-        imm5: i8,
-    },
-    
-    And {
-        dr: u8,
-        sr1: u8,
-        sr2: u8,
-    },
-
-    AndImmediate {
-        dr: u8,
-        sr1: u8,
-        imm5: i8,
-    },
-
-    Branch {
-        nzp: u8,
-        pc_offset9: i16,
-    },
-    
-    Jump {
-        base_r: u8,
-    },
-
-    JumpSubroutine {
-        pc_offset11: i16,
-    },
-
-    JumpSubroutineRegister {
-        base_r: u8,
-    },
-
-    Load {
-        dr: u8,
-        pc_offset9: i16,
-    },
-
-    LoadIndirect {
-        dr: u8,
-        pc_offset9: i16,
-    },
-
-    LoadRegister {
-        dr: u8,
-        base_r: u8,
-        offset6: i8,
-    },
-
-    LoadEffectiveAddress {
-        dr: u8,
-        pc_offset9: i16,
-    },
-
-    Not {
-        dr: u8,
-        sr: u8,
-    },
-
-    Return,
-
-    ReturnInterrupt,
-
-    Store {
-        sr: u8,
-        pc_offset9: i16,
-    },
-
-    StoreIndirect {
-        sr: u8,
-        pc_offset9: i16,
-    },
-
-    StoreRegister {
-        sr: u8,
-        base_r: u8,
-        offset6: i8,
-    },
-
-    Trap {
-        trapvect8: u8,
-    },
-}
-
-impl InstructionData {
-    fn binary(self) -> u16 {
-        match self {
-            Self::Add { dr, sr1, sr2 } => (dr as u16) << 9 | (sr1 as u16) << 6 | (sr2 as u16),
-            Self::AddImmediate { dr, sr1, imm5 } => (dr as u16) << 9 | (sr1 as u16) << 6 | 1 << 5 | (imm5 as u16) & ((1 << 5) - 1),
-            Self::And { dr, sr1, sr2 } => (dr as u16) << 9 | (sr1 as u16) << 6 | (sr2 as u16),
-            Self::AndImmediate { dr, sr1, imm5 } => (dr as u16) << 9 | (sr1 as u16) << 6 | 1 << 5 | (imm5 as u16) & ((1 << 5) - 1),
-            Self::Branch { nzp, pc_offset9 } => (nzp as u16) << 9 | (pc_offset9 as u16) & ((1 << 9) - 1),
-            Self::Jump { base_r } => (base_r as u16) << 6,
-            Self::JumpSubroutine { pc_offset11 } => 1 << 11 | pc_offset11 as u16 & ((1 << 11) - 1),
-            Self::JumpSubroutineRegister { base_r } => (base_r as u16) << 6,
-            Self::Load { dr, pc_offset9 } => (dr as u16) << 9 | (pc_offset9 as u16) & ((1 << 9) - 1),
-            Self::LoadIndirect { dr, pc_offset9 } => (dr as u16) << 9 | (pc_offset9 as u16) & ((1 << 9) - 1),
-            Self::LoadRegister { dr, base_r, offset6 } => (dr as u16) << 9 | (base_r as u16) << 6 | (offset6 as u16) & ((1 << 6) - 1),
-            Self::LoadEffectiveAddress { dr, pc_offset9 } => (dr as u16) << 9 | (pc_offset9 as u16) & ((1 << 9) - 1),
-            Self::Not { dr, sr } => (dr as u16) << 9 | (sr as u16) << 6 | 0b111111,
-            Self::Return => 0b000111000000,
-            Self::ReturnInterrupt => 0b000000000000,
-            Self::Store { sr, pc_offset9 } => (sr as u16) << 9 | (pc_offset9 as u16) & ((1 << 9) - 1),
-            Self::StoreIndirect { sr, pc_offset9 } => (sr as u16) << 9 | (pc_offset9 as u16) & ((1 << 9) - 1),
-            Self::StoreRegister { sr, base_r, offset6 } => (sr as u16) << 9 | (base_r as u16) << 6 | (offset6 as u16) & ((1 << 6) - 1),
-            Self::Trap { trapvect8 } => trapvect8 as u16,
+// labels are written in uppercase, unless they are case-sensitive and so kept as they were stored
+fn symbol_name(label: &str, config: Config) -> String {
+    match config.case_sensitive_labels {
+        true => label.to_string(),
+        false => label.to_uppercase(),
+    }
+}
+
+// a symbol file lists every label along with its address, in address order
+fn render_symbols(symbols: &SymbolTable, config: Config) -> String {
+    let mut symbols = symbols.iter().collect::<Vec<_>>();
+    symbols.sort_by_key(|&(label, address)| (address, label));
+
+    let mut output = String::from("//\tSymbol\tAddress\n");
+    for (label, address) in symbols {
+        output += &format!("{}\tx{:04X}\n", symbol_name(label, config), address);
+    }
+
+    output
+}
+
+fn json_string(s: &str) -> String {
+    let mut json = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => json += "\\\"",
+            '\\' => json += "\\\\",
+            '\n' => json += "\\n",
+            '\t' => json += "\\t",
+            c if c.is_control() => json += &format!("\\u{:04x}", c as u32),
+            c => json.push(c),
         }
     }
+    json.push('"');
+    json
 }
 
-fn parse_register(s: &str) -> Result<u8, String> {
-    let mut chars = s.chars();
-    if let Some('r' | 'R') = chars.next() {
-        if let Some(c) = chars.next() {
-            if let Some(register) = c.to_digit(10) {
-                if register < 8 {
-                    return Ok(register as u8);
-                }
+// a tab-separated table for static analysis with the address, mnemonic or directive, size in
+// words, and category of every statement that takes up memory
+fn render_meta(program: &Program) -> String {
+    let mut output = String::from("address\tmnemonic\tsize\tcategory\n");
+    let mut address = 0u16;
+
+    for (_, statement) in &program.statements {
+        if let Statement::Directive(DirectiveData::Orig { address: start }) = statement {
+            address = *start;
+            continue;
+        }
+
+        let size = statement.words().len();
+        if size > 0 {
+            output += &format!("x{:04X}\t{}\t{}\t{}\n", address, statement.name(), size, statement.category());
+            address = address.wrapping_add(size as u16);
+        }
+    }
+
+    output
+}
+
+// the object file as a c array, to paste into an emulator
+fn render_c_array(program: &Program, name: &str) -> String {
+    let words = program.words().iter().map(|word| format!("0x{:04X}", word)).collect::<Vec<_>>();
+    format!("uint16_t {}[] = {{ {} }};\n", name, words.join(", "))
+}
+
+// an error followed by the line it is on, with a caret under the token it is about when that can
+// be found, where the prefix is red on a terminal
+fn render_error(source: &str, prefix: &str, error: &AssembleError, color: bool) -> String {
+    let mut output = match color {
+        true => format!("\x1b[1;31merror\x1b[0m: {}{}\n", prefix, error),
+        false => format!("error: {}{}\n", prefix, error),
+    };
+
+    let Some(text) = source.lines().nth(error.line.wrapping_sub(1)) else {
+        return output;
+    };
+
+    // the caret is as wide as the token, or a single character when the error isn't about one
+    let column = error.column.map(|column| (column - 1, error.kind.token().map_or(1, |token| token.chars().count())));
+
+    let gutter = " ".repeat(error.line.to_string().len());
+    output += &format!("{} | {}\n", error.line, text);
+    if let Some((column, width)) = column {
+        output += &format!("{} | {}{}\n", gutter, " ".repeat(column), "^".repeat(width));
+    }
+
+    output
+}
+
+// a memory init file for $readmemh, one hex word to a line after an @ marker with the origin
+// (unless it is left out so the memory is filled from address 0)
+fn render_mem(program: &Program, address: bool) -> String {
+    let mut output = match address {
+        true => format!("@{:04X}\n", program.origin()),
+        false => String::new(),
+    };
+
+    for (_, statement) in &program.statements {
+        if !matches!(statement, Statement::Directive(DirectiveData::Orig { .. })) {
+            for word in statement.words() {
+                output += &format!("{:04X}\n", word);
             }
         }
     }
 
-    return Err("Invalid register".into());
+    output
+}
+
+// an intel hex record, whose checksum makes every byte of it sum to 0
+fn ihex_record(kind: u8, address: u16, data: &[u8]) -> String {
+    let bytes = [&[data.len() as u8], &address.to_be_bytes()[..], &[kind], data].concat();
+    let checksum = bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)).wrapping_neg();
+    let hex = bytes.iter().chain([&checksum]).map(|byte| format!("{:02X}", byte)).collect::<String>();
+    format!(":{}\n", hex)
 }
 
-fn parse<'a>(args: &mut &[&str]) -> Result<(Instruction, InstructionData), String>
-{
-    if args.is_empty() {
-        return Err("No instruction".into());
+// intel hex of the program's words (the origin word left out), big-endian like an object file
+//
+// records are byte-addressed, so a word at address a is at bytes 2a and 2a + 1, and an extended
+// linear address record gives the upper bits of addresses past xFFFF bytes
+fn render_ihex(program: &Program) -> String {
+    let bytes = program
+        .statements
+        .iter()
+        .filter(|(_, statement)| !matches!(statement, Statement::Directive(DirectiveData::Orig { .. })))
+        .flat_map(|(_, statement)| statement.words())
+        .flat_map(|word| word.to_be_bytes())
+        .collect::<Vec<_>>();
+
+    let mut output = String::new();
+    let mut upper = 0;
+    let mut address = program.origin() as u32 * 2;
+
+    for chunk in bytes.chunks(16) {
+        if address >> 16 != upper {
+            upper = address >> 16;
+            output += &ihex_record(0x04, 0, &(upper as u16).to_be_bytes());
+        }
+
+        // a record can't run past the end of its 64k segment, so one that would is split there
+        let room = (0x10000 - (address & 0xFFFF)) as usize;
+        let (first, rest) = chunk.split_at(chunk.len().min(room));
+        output += &ihex_record(0x00, address as u16, first);
+        address += first.len() as u32;
+
+        if !rest.is_empty() {
+            upper = address >> 16;
+            output += &ihex_record(0x04, 0, &(upper as u16).to_be_bytes());
+            output += &ihex_record(0x00, address as u16, rest);
+            address += rest.len() as u32;
+        }
     }
 
-    let instruction = Instruction::try_from(args[0])?;
-    *args = &args[1..];
+    output + &ihex_record(0x01, 0, &[])
+}
 
-    if instruction.num_args() > args.len() {
-        return Err("Invalid number of arguments".into());
+// a memory dump of the program, eight words to a row after the address of the first
+fn render_dump(program: &Program) -> String {
+    let words = program
+        .statements
+        .iter()
+        .filter(|(_, statement)| !matches!(statement, Statement::Directive(DirectiveData::Orig { .. })))
+        .flat_map(|(_, statement)| statement.words())
+        .collect::<Vec<_>>();
+
+    let mut output = String::new();
+    for (row, chunk) in words.chunks(8).enumerate() {
+        output += &format!("x{:04X}:", program.origin().wrapping_add(row as u16 * 8));
+        for word in chunk {
+            output += &format!(" {:04X}", word);
+        }
+        output.push('\n');
     }
 
-    let instruction_data = match instruction {
-        Instruction::Add => {
-            let dr = parse_register(args[0])?;
-            let sr1 = parse_register(args[1])?;
+    output
+}
+
+// every mnemonic and directive json for editor plugins, with how many operands each takes (at
+// most) and what it assembles to
+fn render_mnemonics() -> String {
+    let instructions = Instruction::ALL.into_iter().map(|instruction| format!(
+        "    {{ \"mnemonic\": {}, \"arity\": {}, \"encoding\": {} }}",
+        json_string(&instruction.name().to_lowercase()), instruction.num_args(), json_string(instruction.encoding()),
+    ));
+    let mut directives = Directive::ALL.into_iter().map(|directive| format!(
+        "    {{ \"mnemonic\": {}, \"arity\": {}, \"encoding\": {} }}",
+        json_string(&directive.name().to_lowercase()), directive.num_args(), json_string(directive.description()),
+    )).collect::<Vec<_>>();
+    // a constant is defined in the first pass rather than being a directive of its own
+    directives.push("    { \"mnemonic\": \".equ\", \"arity\": 2, \"encoding\": \"nothing, it names a constant\" }".into());
+
+    format!(
+        "{{\n  \"instructions\": [\n{}\n  ],\n  \"directives\": [\n{}\n  ]\n}}\n",
+        instructions.collect::<Vec<_>>().join(",\n"), directives.join(",\n"),
+    )
+}
+
+// the line a statement was written on, or nothing for one the assembler added itself
+fn source_text<'a>(lines: &[&'a str], span: &Span) -> &'a str {
+    match span.columns.is_empty() {
+        true => "",
+        false => lines[span.line - 1],
+    }
+}
+
+// json for tooling holds the origin, the symbol table, and every word of the program along with
+// its address, the source line it came from, and the mnemonic or directive that produced it
+fn render_json(source: &str, program: &Program, entry: u16, config: Config) -> String {
+    let lines = source.lines().collect::<Vec<_>>();
+    let mut words = Vec::new();
+    let mut origin = 0;
+    let mut address = 0u16;
+
+    for (span, statement) in &program.statements {
+        if let Statement::Directive(DirectiveData::Orig { address: start }) = statement {
+            origin = *start;
+            address = *start;
+            continue;
+        }
+
+        for word in statement.words() {
+            words.push(format!(
+                "    {{ \"address\": {}, \"word\": {}, \"source\": {}, \"mnemonic\": {} }}",
+                address, word, json_string(source_text(&lines, span).trim()), json_string(statement.name()),
+            ));
+            address = address.wrapping_add(1);
+        }
+    }
+
+    let mut symbols = program.symbols.iter().collect::<Vec<_>>();
+    symbols.sort_by_key(|&(label, address)| (address, label));
+    let symbols = symbols
+        .into_iter()
+        .map(|(label, address)| format!("    {}: {}", json_string(&symbol_name(label, config)), address))
+        .collect::<Vec<_>>();
+
+    format!(
+        "{{\n  \"origin\": {},\n  \"entry\": {},\n  \"symbols\": {{\n{}\n  }},\n  \"words\": [\n{}\n  ]\n}}\n",
+        origin, entry, symbols.join(",\n"), words.join(",\n"),
+    )
+}
+
+// a listing shows every source line next to the address and machine code of each word it emits,
+// where the origin word has no address of its own
+fn render_listing(source: &str, program: &Program) -> String {
+    let lines = source.lines().collect::<Vec<_>>();
+    let mut output = String::new();
+    let mut listed = 0;
+    let mut address = 0u16;
+
+    let mut list = |address: Option<u16>, word: Option<u16>, text: &str| {
+        let address = address.map_or(" ".repeat(4), |address| format!("{:04X}", address));
+        let word = word.map_or(" ".repeat(22), |word| format!("{:04X}  {:016b}", word, word));
+        output += format!("{}  {}  {}", address, word, text).trim_end();
+        output += "\n";
+    };
 
-            if let Ok(sr2) = parse_register(args[2]) {
-                InstructionData::Add { dr, sr1, sr2 }
-            } else {
-                let imm5 = parse_uint::<i8>(args[2]).unwrap();
-                InstructionData::AddImmediate { dr, sr1, imm5 }
+    for (span, statement) in &program.statements {
+        // a statement that was never written (an origin that came from the command line, or
+        // padding) gets a row of its own, leaving the line it is credited to with its own words
+        if span.columns.is_empty() {
+            match statement {
+                Statement::Directive(DirectiveData::Orig { address: origin }) => {
+                    address = *origin;
+                    list(None, Some(*origin), "");
+                },
+                _ => {
+                    for word in statement.words() {
+                        list(Some(address), Some(word), "");
+                        address = address.wrapping_add(1);
+                    }
+                },
             }
-        },
-        Instruction::And => {
-            let dr = parse_register(args[0])?;
-            let sr1 = parse_register(args[1])?;
-
-            if let Ok(sr2) = parse_register(args[2]) {
-                InstructionData::And { dr, sr1, sr2 }
-            } else {
-                let imm5 = parse_uint::<i8>(args[2]).unwrap();
-                InstructionData::AndImmediate { dr, sr1, imm5 }
+            continue;
+        }
+
+        let line = span.line;
+        // lines without a statement of their own (comments, labels, blank lines) are listed as is
+        while listed + 1 < line {
+            list(None, None, lines[listed]);
+            listed += 1;
+        }
+
+        let text = match listed < line {
+            true => {
+                listed = line;
+                lines[line - 1]
+            },
+            false => "",
+        };
+
+        match statement {
+            Statement::Directive(DirectiveData::Orig { address: origin }) => {
+                address = *origin;
+                list(None, Some(*origin), text);
+            },
+            _ => {
+                let words = statement.words();
+                if words.is_empty() {
+                    list(None, None, text);
+                }
+
+                for (i, word) in words.into_iter().enumerate() {
+                    list(Some(address), Some(word), if i == 0 { text } else { "" });
+                    address = address.wrapping_add(1);
+                }
+            },
+        }
+    }
+
+    for text in &lines[listed.min(lines.len())..] {
+        list(None, None, text);
+    }
+
+    output
+}
+
+// what went wrong, which decides the exit code
+enum Failure {
+    // the command line didn't make sense
+    Usage(String),
+    // an input couldn't be read or an output couldn't be written
+    Io(String),
+    // an input was read but isn't valid
+    Assembly(String),
+    // whatever went wrong was already reported, so only the exit code is left
+    Reported(i32),
+}
+
+impl Failure {
+    fn code(&self) -> i32 {
+        match self {
+            Self::Assembly(_) => 1,
+            Self::Usage(_) | Self::Io(_) => 2,
+            Self::Reported(code) => *code,
+        }
+    }
+}
+
+impl std::fmt::Display for Failure {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Usage(e) | Self::Io(e) | Self::Assembly(e) => write!(f, "{}", e),
+            Self::Reported(_) => Ok(()),
+        }
+    }
+}
+
+const USAGE: &str = "\
+usage: lc3-assembler [--format=bits|hex|bin|json|meta|dump|c|mem|ihex] [--c-array[=<name>]] [--no-mem-address] [--with-address] [--sym] [--lst] [--verbose] [--no-comments] [--checksum] [--entry=<label>] [--max-words=<n>] [--origin=<address>] [--default-origin=<address>] [--pad-to=<address>] [--raw] [--strict] [--case-sensitive-labels] [--warn-truncation] [--warn-self-loop] [--no-aliases] [--stop-on-error] [--check] [--max-errors=<n>] [--stream] [<file>...]
+       lc3-assembler --disasm [<file.obj>...]
+reads from stdin when no file is given
+--entry reports where execution starts (the origin by default), and is written to json output
+--origin relocates a program to another address than its .orig, or gives it one when it has none
+--check only reports errors, writing nothing
+--default-origin gives a program without a .orig one, with a warning
+--with-address starts each bits or hex line with the address of its word
+--stream writes each word as soon as it is assembled, without comments or anything else that needs the whole program
+--format=ihex addresses bytes, so the word at address a is at bytes 2a and 2a + 1
+--max-errors reports at most that many errors for each input (20 by default)
+--warn-self-loop warns about an unconditional branch to itself, which never stops (HALT does)
+--no-aliases turns off the register aliases sp (r6) and fp (r5)
+--raw assembles a snippet with no .orig at address 0, and its output isn't a loadable object
+exits with 0 on success, 1 when an input doesn't assemble, and 2 on usage or i/o errors";
+
+// assembles (or disassembles) a single input, where the output goes to stdout unless it is an
+// object file or there are several inputs, in which case it is written next to the input
+fn process(path: Option<&str>, options: &Options, multiple: bool) -> Result<(), Failure> {
+    let name = path.unwrap_or("<stdin>");
+    let bytes = read_input(path).map_err(|e| Failure::Io(format!("{}: {}", name, e)))?;
+
+    let prefix = if multiple { format!("{}: ", name) } else { String::new() };
+
+    let (output, extension) = if options.disasm {
+        let words = object_words(&bytes).map_err(|e| Failure::Assembly(format!("{}: {}", name, e)))?;
+        let (lines, warnings) = disassemble(&words);
+        report_warnings(&prefix, &warnings);
+        let lines = lines.into_iter().map(|line| line + "\n").collect::<String>();
+        (lines.into_bytes(), "dis.asm")
+    } else {
+        let source = String::from_utf8(bytes).map_err(|e| Failure::Assembly(format!("{}: {}", name, e)))?;
+        let source = normalize_source(&source);
+
+        // words go straight to the output as they are assembled, for sources too big to keep
+        // around, which is stdout or a file next to the input the same as for the whole program
+        //
+        // a file is written under another name and only renamed once the whole program has
+        // assembled, so an error leaves nothing behind just like it does for the whole program
+        if options.stream {
+            let target = match path {
+                Some(path) if multiple || matches!(options.format, Format::Bin) => {
+                    let path = std::path::Path::new(path).with_extension(options.format.extension());
+                    Some((path.with_extension(format!("{}.partial", options.format.extension())), path))
+                },
+                _ => None,
+            };
+            let (sink, sink_name): (Box<dyn Write>, String) = match &target {
+                Some((partial, _)) => {
+                    let file = std::fs::File::create(partial).map_err(|e| Failure::Io(format!("{}: {}", partial.display(), e)))?;
+                    (Box::new(file), partial.display().to_string())
+                },
+                None => (Box::new(std::io::stdout().lock()), "<stdout>".into()),
+            };
+            let mut out = std::io::BufWriter::new(sink);
+            let mut written = Ok(());
+            let result = assemble_streaming(&source, options.config, |word| {
+                if written.is_ok() {
+                    written = match options.format {
+                        Format::Bin => out.write_all(&word.to_be_bytes()),
+                        Format::Hex => writeln!(out, "0x{:04X}", word),
+                        _ => writeln!(out, "{:016b}", word),
+                    };
+                }
+            });
+            let written = written.and_then(|_| out.flush());
+            drop(out);
+
+            let warnings = match (result, written) {
+                (Ok(warnings), Ok(())) => warnings,
+                (result, written) => {
+                    if let Some((partial, _)) = &target {
+                        let _ = std::fs::remove_file(partial);
+                    }
+                    return Err(match result {
+                        Err(e) => {
+                            eprint!("{}", render_error(&source, &prefix, &e, std::io::stderr().is_terminal()));
+                            Failure::Reported(1)
+                        },
+                        Ok(_) => Failure::Io(format!("{}: {}", sink_name, written.unwrap_err())),
+                    });
+                },
+            };
+            report_warnings(&prefix, &warnings);
+            if let Some((partial, path)) = &target {
+                std::fs::rename(partial, path).map_err(|e| Failure::Io(format!("{}: {}", path.display(), e)))?;
             }
-        },
-        Instruction::Branch => {
-            let mut nzp = 0;
-            if args[0].contains('n') {
-                nzp |= 0b100;
+            return Ok(());
+        }
+
+        // every error is reported in line order, rather than just the first
+        let program = assemble_reporting_all(&source, options.config).map_err(|mut errors| {
+            errors.sort_by_key(|e| e.line);
+            for e in errors.iter().take(options.max_errors) {
+                eprint!("{}", render_error(&source, &prefix, e, std::io::stderr().is_terminal()));
             }
-            if args[0].contains('z') {
-                nzp |= 0b010;
+            if errors.len() > options.max_errors {
+                let more = errors.len() - options.max_errors;
+                eprintln!("... and {} more error{}", more, if more == 1 { "" } else { "s" });
             }
-            if args[0].contains('p') {
-                nzp |= 0b001;
+            Failure::Reported(1)
+        })?;
+        report_warnings(&prefix, &program.warnings);
+
+        if options.check {
+            return Ok(());
+        }
+
+        // goes to stderr so stdout only ever holds the output
+        if options.verbose {
+            eprint!("{}", render_symbols(&program.symbols, options.config));
+            eprintln!("end address: x{:04X}", program.end);
+        }
+
+        // execution starts at the origin unless another label is given
+        let entry = match &options.entry {
+            Some(label) => {
+                let entry = program.address_of(label, options.config)
+                    .ok_or_else(|| Failure::Assembly(format!("{}: entry label '{}' is undefined", name, label)))?;
+                eprintln!("{}: entry x{:04X}", name, entry);
+                entry
+            },
+            None => program.origin(),
+        };
+
+        if let Some(max_words) = options.max_words {
+            let size = program.end - program.origin() as u32;
+            if size > max_words {
+                return Err(Failure::Assembly(format!("{}: program is {} words, over the limit of {}", name, size, max_words)));
             }
+        }
 
-            let pc_offset9 = parse_int::<i16>(args[1]).unwrap();
-            InstructionData::Branch { nzp, pc_offset9 }
-        },
-        Instruction::Jump => {
-            let base_r = parse_register(args[0])?;
-            InstructionData::Jump { base_r }
-        },
-        Instruction::JumpSubroutine => {
-            let pc_offset11 = parse_int::<i16>(args[0]).unwrap();
-            InstructionData::JumpSubroutine { pc_offset11 }
-        },
-        Instruction::JumpSubroutineRegister => {
-            let base_r = parse_register(args[0])?;
-            InstructionData::JumpSubroutineRegister { base_r }
-        },
-        Instruction::Load => {
-            let dr = parse_register(args[0])?;
-            let pc_offset9 = parse_int::<i16>(args[1]).unwrap();
-            InstructionData::Load { dr, pc_offset9 }
-        },
-        Instruction::LoadIndirect => {
-            let dr = parse_register(args[0])?;
-            let pc_offset9 = parse_uint::<i16>(args[1]).unwrap();
-            InstructionData::LoadIndirect { dr, pc_offset9 }
-        },
-        Instruction::LoadRegister => {
-            let dr = parse_register(args[0])?;
-            let base_r = parse_register(args[1])?;
-            let offset6 = parse_int::<i8>(args[2]).unwrap();
-            InstructionData::LoadRegister { dr, base_r, offset6 }
-        },
-        Instruction::LoadEffectiveAddress => {
-            let dr = parse_register(args[0])?;
-            let pc_offset9 = parse_int::<i16>(args[1]).unwrap();
-            InstructionData::LoadEffectiveAddress { dr, pc_offset9 }
-        },
-        Instruction::Not => {
-            let dr = parse_register(args[0])?;
-            let sr = parse_register(args[1])?;
-            InstructionData::Not { dr, sr }
-        },
-        Instruction::Return => InstructionData::Return,
-        Instruction::ReturnInterrupt => InstructionData::ReturnInterrupt,
-        Instruction::Store => {
-            let sr = parse_register(args[0])?;
-            let pc_offset9 = parse_int::<i16>(args[1]).unwrap();
-            InstructionData::Store { sr, pc_offset9 }
-        },
-        Instruction::StoreIndirect => {
-            let sr = parse_register(args[0])?;
-            let pc_offset9 = parse_int::<i16>(args[1]).unwrap();
-            InstructionData::StoreIndirect { sr, pc_offset9 }
-        },
-        Instruction::StoreRegister => {
-            let sr = parse_register(args[0])?;
-            let base_r = parse_register(args[1])?;
-            let offset6 = parse_int::<i8>(args[2]).unwrap();
-            InstructionData::StoreRegister { sr, base_r, offset6 }
-        },
-        Instruction::Trap => {
-            let trapvect8 = parse_uint::<u8>(args[0]).unwrap();
-            InstructionData::Trap { trapvect8 }
-        },
+        if options.checksum {
+            eprintln!("{}: checksum x{:04X}", name, checksum(&program.words()));
+        }
+
+        if options.sym {
+            let path = path.ok_or_else(|| Failure::Usage("--sym needs an input file to name the symbol file after".into()))?;
+            write_output(&std::path::Path::new(path).with_extension("sym"), render_symbols(&program.symbols, options.config).as_bytes())?;
+        }
+
+        if options.lst {
+            let path = path.ok_or_else(|| Failure::Usage("--lst needs an input file to name the listing file after".into()))?;
+            write_output(&std::path::Path::new(path).with_extension("lst"), render_listing(&source, &program).as_bytes())?;
+        }
+
+        (render(&source, &program, options, entry), options.format.extension())
     };
 
-    *args = &args[instruction.num_args()..];
-    Ok((instruction, instruction_data))
+    match path {
+        Some(path) if multiple || matches!(options.format, Format::Bin) && !options.disasm => {
+            write_output(&std::path::Path::new(path).with_extension(extension), &output)
+        },
+        _ => std::io::stdout().write_all(&output).map_err(|e| Failure::Io(format!("<stdout>: {}", e))),
+    }
 }
 
-struct Tokenizer<'a> {
-    input: &'a str,
-    pos: usize,
+fn report_warnings(prefix: &str, warnings: &[Warning]) {
+    for warning in warnings {
+        eprintln!("warning: {}{}", prefix, warning);
+    }
 }
 
-impl<'a> Iterator for Tokenizer<'a> {
-    type Item = &'a str;
+fn write_output(path: &std::path::Path, output: &[u8]) -> Result<(), Failure> {
+    std::fs::write(path, output).map_err(|e| Failure::Io(format!("{}: {}", path.display(), e)))
+}
 
-    fn next(&mut self) -> Option<Self::Item> {
-        let mut chars = self.input[self.pos..].chars();
-        let mut count = 0;
+fn run() -> Result<(), Failure> {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
 
-        while let Some(c) = chars.next() {
-            if c.is_whitespace() || c == ',' {
-                if count > 0 {
-                    break;
-                } else {
-                    self.pos += 1;
-                }
-            } else {
-                count += 1;
+    let mut paths = Vec::new();
+    let mut options = Options {
+        format: Format::Bits,
+        disasm: false,
+        stop_on_error: false,
+        check: false,
+        stream: false,
+        max_errors: 20,
+        sym: false,
+        lst: false,
+        verbose: false,
+        comments: true,
+        checksum: false,
+        entry: None,
+        max_words: None,
+        c_array: "program".into(),
+        mem_address: true,
+        with_address: false,
+        config: Config::default(),
+    };
+
+    for arg in &args {
+        if arg == "--list-mnemonics" {
+            print!("{}", render_mnemonics());
+            return Ok(());
+        } else if arg == "--disasm" {
+            options.disasm = true;
+        } else if arg == "--stop-on-error" {
+            options.stop_on_error = true;
+        } else if arg == "--sym" {
+            options.sym = true;
+        } else if arg == "--lst" {
+            options.lst = true;
+        } else if arg == "--verbose" {
+            options.verbose = true;
+        } else if arg == "--json" {
+            options.format = Format::Json;
+        } else if arg == "--stream" {
+            options.stream = true;
+        } else if arg == "--check" {
+            options.check = true;
+        } else if arg == "--c-array" {
+            options.format = Format::CArray;
+        } else if let Some(name) = arg.strip_prefix("--c-array=") {
+            let mut chars = name.chars();
+            if !chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_') || !chars.all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                return Err(Failure::Usage(format!("Invalid array name '{}'", name)));
             }
+            options.format = Format::CArray;
+            options.c_array = name.to_string();
+        } else if arg == "--no-mem-address" {
+            options.mem_address = false;
+        } else if arg == "--with-address" {
+            options.with_address = true;
+        } else if arg == "--dump" {
+            options.format = Format::Dump;
+        } else if arg == "--meta" {
+            options.format = Format::Meta;
+        } else if arg == "--checksum" {
+            options.checksum = true;
+        } else if arg == "--no-comments" {
+            options.comments = false;
+        } else if arg == "--raw" {
+            options.config.raw = true;
+        } else if arg == "--strict" {
+            options.config.strict = true;
+        } else if arg == "--no-aliases" {
+            options.config.no_register_aliases = true;
+        } else if arg == "--warn-self-loop" {
+            options.config.warn_self_loop = true;
+        } else if arg == "--warn-truncation" {
+            options.config.warn_truncation = true;
+        } else if arg == "--case-sensitive-labels" {
+            options.config.case_sensitive_labels = true;
+        } else if let Some(label) = arg.strip_prefix("--entry=") {
+            options.entry = Some(label.to_string());
+        } else if let Some(value) = arg.strip_prefix("--origin=") {
+            let origin = parse_address(value).map_err(|e| Failure::Usage(format!("{} for --origin", e)))?;
+            options.config.origin = Some(origin);
+        } else if let Some(value) = arg.strip_prefix("--default-origin=") {
+            let origin = parse_address(value).map_err(|e| Failure::Usage(format!("{} for --default-origin", e)))?;
+            options.config.default_origin = Some(origin);
+        } else if let Some(value) = arg.strip_prefix("--pad-to=") {
+            let pad_to = parse_address(value).map_err(|e| Failure::Usage(format!("{} for --pad-to", e)))?;
+            options.config.pad_to = Some(pad_to);
+        } else if let Some(value) = arg.strip_prefix("--max-errors=") {
+            options.max_errors = value.parse().map_err(|_| Failure::Usage(format!("Invalid error limit '{}'", value)))?;
+        } else if let Some(value) = arg.strip_prefix("--max-words=") {
+            let max_words = value.parse().map_err(|_| Failure::Usage(format!("Invalid word limit '{}'", value)))?;
+            options.max_words = Some(max_words);
+        } else if let Some(value) = arg.strip_prefix("--format=") {
+            options.format = Format::try_from(value)
+                .map_err(|e| Failure::Usage(format!("{} '{}' (expected bits, hex, bin, json, meta, dump, c, mem, or ihex)", e, value)))?;
+        } else {
+            paths.push(arg.as_str());
         }
+    }
 
-        if count > 0 {
-            let s = Some(&self.input[self.pos..self.pos + count]);
-            self.pos += count;
-            s
-        } else {
-            None
+    if options.stream && !matches!(options.format, Format::Bits | Format::Hex | Format::Bin) {
+        return Err(Failure::Usage("--stream only writes bits, hex, or bin".into()));
+    }
+
+    // the streaming path never holds the whole program, so nothing that needs all of it can be used with it
+    if options.stream {
+        let needs_program = [
+            (options.check, "--check"),
+            (options.checksum, "--checksum"),
+            (options.max_words.is_some(), "--max-words"),
+            (options.entry.is_some(), "--entry"),
+            (options.sym, "--sym"),
+            (options.lst, "--lst"),
+            (options.verbose, "--verbose"),
+            (options.with_address, "--with-address"),
+        ];
+        if let Some((_, flag)) = needs_program.into_iter().find(|&(set, _)| set) {
+            return Err(Failure::Usage(format!("{} can't be used with --stream, which doesn't keep the program", flag)));
         }
     }
-}
 
-fn main() {
-    let args = std::env::args().skip(1).collect::<Vec<_>>();
-    let file_content = std::fs::read_to_string(&args[0]).unwrap().to_lowercase();
-    let tokens = Tokenizer { input: &file_content, pos: 0 }.collect::<Vec<_>>();
-    let mut token_slice = tokens.as_slice();
+    if options.config.raw && (options.config.origin.is_some() || options.config.default_origin.is_some()) {
+        return Err(Failure::Usage("--origin and --default-origin can't be used with --raw, which has no origin".into()));
+    }
+
+    // with no path the source is piped in, unless nothing is being piped at all
+    if paths.is_empty() {
+        if std::io::stdin().is_terminal() {
+            eprintln!("{}", USAGE);
+            return Err(Failure::Reported(2));
+        }
+
+        return process(None, &options, false);
+    }
 
-    let mut results = Vec::new();
+    // every input is assembled on its own, so one failing doesn't affect the others
+    let multiple = paths.len() > 1;
+    let mut failed = Vec::new();
+    let mut code = 0;
 
+    for path in &paths {
+        if let Err(e) = process(Some(path), &options, multiple) {
+            if !multiple {
+                return Err(e);
+            }
+
+            if !matches!(e, Failure::Reported(_)) {
+                eprintln!("error: {}", e);
+            }
+            failed.push(*path);
+            code = code.max(e.code());
 
-    while token_slice.len() > 0 {
-        results.push(parse(&mut token_slice).unwrap());
+            if options.stop_on_error {
+                break;
+            }
+        }
+    }
+
+    if !failed.is_empty() {
+        eprintln!("{} of {} files failed:", failed.len(), paths.len());
+        for path in &failed {
+            eprintln!("    {}", path);
+        }
+
+        // the worst failure decides the exit code
+        return Err(Failure::Reported(code));
     }
 
-    for ((instruction, instruction_data), line) in results.into_iter().zip(file_content.lines()) {
-        println!("{:04b}{:012b} // {}", instruction.binary(), instruction_data.binary(), line.to_uppercase());
+    Ok(())
+}
+
+fn main() {
+    if let Err(e) = run() {
+        if !matches!(e, Failure::Reported(_)) {
+            eprintln!("error: {}", e);
+        }
+
+        std::process::exit(e.code());
     }
 }
@@ -0,0 +1,317 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+// runs the assembler with the given arguments and source on stdin, which is piped so stderr is
+// never a terminal, and which it may not read at all when it stops at a usage error
+fn run(args: &[&str], source: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_lc3-assembler"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    if let Err(e) = child.stdin.take().unwrap().write_all(source.as_bytes()) {
+        assert_eq!(e.kind(), std::io::ErrorKind::BrokenPipe);
+    }
+    child.wait_with_output().unwrap()
+}
+
+// writes a source file where the assembler can put its output next to it, in a directory of its
+// own so tests running at the same time don't share files, and emptied so no earlier run's
+// output is left in it
+fn source_file(test: &str, source: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(test);
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("program.asm");
+    std::fs::write(&path, source).unwrap();
+    path
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8(output.stderr.clone()).unwrap()
+}
+
+#[test]
+fn caret_under_the_bad_token_without_color() {
+    let output = run(&[], ".orig x3000\nLOOPX BR LOOP\n.end\n");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        stderr(&output),
+        "error: line 2: Undefined label or invalid offset 'LOOP'\n2 | LOOPX BR LOOP\n  |          ^^^^\n",
+    );
+}
+
+#[test]
+fn object_file_is_big_endian_with_the_origin_first() {
+    let path = source_file("object_file", ".orig x3000\nHALT\n.fill x1234\n.end\n");
+    let output = run(&["--format=bin", path.to_str().unwrap()], "");
+    assert!(output.status.success());
+    let bytes = std::fs::read(path.with_extension("obj")).unwrap();
+    assert_eq!(bytes, [0x30, 0x00, 0xF0, 0x25, 0x12, 0x34]);
+}
+
+#[test]
+fn every_word_of_a_string_is_annotated_with_its_line() {
+    let output = run(&["--format=hex"], ".orig x3000\n.stringz \"Hi!\"\n.end\n");
+    assert_eq!(
+        stdout(&output),
+        "0x3000 // .orig x3000\n0x0048 // .stringz \"Hi!\"\n0x0069 // .stringz \"Hi!\"\n0x0021 // .stringz \"Hi!\"\n0x0000 // .stringz \"Hi!\"\n",
+    );
+
+    let output = run(&["--format=hex", "--no-comments"], ".orig x3000\n.stringz \"Hi!\"\n.end\n");
+    assert_eq!(stdout(&output), "0x3000\n0x0048\n0x0069\n0x0021\n0x0000\n");
+}
+
+#[test]
+fn checksum_goes_to_stderr() {
+    let output = run(&["--checksum", "--format=hex", "--no-comments"], ".orig x3000\nHALT\n.end\n");
+    assert!(output.status.success());
+    assert_eq!(stderr(&output), "<stdin>: checksum x2025\n");
+    assert_eq!(stdout(&output), "0x3000\n0xF025\n");
+}
+
+#[test]
+fn entry_is_resolved_from_a_label() {
+    let source = ".orig x3000\nHALT\nMAIN ADD R0 R0 R0\n.end\n";
+    let output = run(&["--entry=MAIN", "--format=json"], source);
+    assert!(output.status.success());
+    assert_eq!(stderr(&output), "<stdin>: entry x3001\n");
+    assert!(stdout(&output).contains("\"entry\": 12289,"));
+
+    let output = run(&["--entry=NOPE"], source);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stderr(&output), "error: <stdin>: entry label 'NOPE' is undefined\n");
+}
+
+#[test]
+fn byte_order_mark_and_crlf_are_ignored() {
+    let output = run(&["--format=hex", "--no-comments"], "\u{feff}.orig x3000\r\nHALT\r\n.end\r\n");
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "0x3000\n0xF025\n");
+}
+
+#[test]
+fn max_words_counts_every_word_past_the_origin() {
+    let output = run(&["--max-words=4"], ".orig x3000\n.blkw 5\n.end\n");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stderr(&output), "error: <stdin>: program is 5 words, over the limit of 4\n");
+
+    // the last word is at xFFFF, so the end address is one past the top of memory
+    let output = run(&["--max-words=1"], ".orig xFFFE\n.blkw 2\n.end\n");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).ends_with("error: <stdin>: program is 2 words, over the limit of 1\n"));
+    assert!(run(&["--max-words=2"], ".orig xFFFE\n.blkw 2\n.end\n").status.success());
+}
+
+#[test]
+fn origin_relocates_absolute_label_values() {
+    let output = run(&["--origin=x4000", "--format=hex", "--no-comments"], ".orig x3000\nSELF HALT\n.fill SELF\n.end\n");
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "0x4000\n0xF025\n0x4000\n");
+    assert_eq!(stderr(&output), "warning: line 1: .orig x3000 is overridden by x4000\n");
+
+    let output = run(&["--origin=x4000", "--format=hex", "--no-comments"], "SELF HALT\n.fill SELF\n.end\n");
+    assert_eq!(stdout(&output), "0x4000\n0xF025\n0x4000\n");
+    assert_eq!(stderr(&output), "");
+}
+
+#[test]
+fn meta_categorizes_each_statement() {
+    let output = run(&["--format=meta"], ".orig x3000\nADD R0 R0 R0\nLD R0 #0\nBR #0\n.blkw 2\n.end\n");
+    assert_eq!(
+        stdout(&output),
+        "address\tmnemonic\tsize\tcategory\nx3000\tADD\t1\talu\nx3001\tLD\t1\tmemory\nx3002\tBR\t1\tcontrol\nx3003\t.BLKW\t2\tdata\n",
+    );
+}
+
+#[test]
+fn check_reports_every_error_and_writes_nothing() {
+    let output = run(&["--check"], ".orig x3000\nHALT R0\nADD R0\n.end\n");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stdout(&output), "");
+    assert_eq!(
+        stderr(&output),
+        "error: line 2: HALT takes no operands\n2 | HALT R0\nerror: line 3: ADD expects 3 operands, found 1\n3 | ADD R0\n",
+    );
+
+    let output = run(&["--check"], ".orig x3000\nHALT\n.end\n");
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "");
+}
+
+#[test]
+fn dump_puts_eight_words_on_a_row() {
+    let output = run(&["--format=dump"], ".orig x3000\n.blkw 8, x1\n.fill x2\n.fill x3\n.end\n");
+    assert_eq!(stdout(&output), "x3000: 0001 0001 0001 0001 0001 0001 0001 0001\nx3008: 0002 0003\n");
+}
+
+#[test]
+fn c_array_holds_every_word_with_the_origin() {
+    let output = run(&["--format=c"], ".orig x3000\nHALT\n.end\n");
+    assert_eq!(stdout(&output), "uint16_t program[] = { 0x3000, 0xF025 };\n");
+    let output = run(&["--c-array=boot"], ".orig x3000\nHALT\n.end\n");
+    assert_eq!(stdout(&output), "uint16_t boot[] = { 0x3000, 0xF025 };\n");
+}
+
+#[test]
+fn mem_starts_at_the_origin_marker() {
+    let output = run(&["--format=mem"], ".orig x3000\nHALT\n.fill 1\n.end\n");
+    assert_eq!(stdout(&output), "@3000\nF025\n0001\n");
+    let output = run(&["--format=mem", "--no-mem-address"], ".orig x3000\nHALT\n.fill 1\n.end\n");
+    assert_eq!(stdout(&output), "F025\n0001\n");
+}
+
+#[test]
+fn list_mnemonics_gives_each_arity() {
+    let output = run(&["--list-mnemonics"], "");
+    assert!(output.status.success());
+    let list = stdout(&output);
+    assert!(list.contains("{ \"mnemonic\": \"add\", \"arity\": 3, "));
+    assert!(list.contains("{ \"mnemonic\": \"ret\", \"arity\": 0, "));
+}
+
+#[test]
+fn default_origin_only_applies_without_an_orig() {
+    let output = run(&["--default-origin=x3000", "--format=hex", "--no-comments"], "HALT\n.end\n");
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "0x3000\n0xF025\n");
+    assert_eq!(stderr(&output), "warning: line 1: no .orig, assuming x3000\n");
+
+    let output = run(&["--format=hex"], "HALT\n.end\n");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stdout(&output), "");
+    assert_eq!(stderr(&output), "error: line 1: Statement before .orig (HALT)\n1 | HALT\n");
+}
+
+#[test]
+fn stream_writes_the_same_words_as_the_whole_program() {
+    let source = ".orig x3000\nLOOP BR LOOP\n.stringz \"ab\"\n.blkw 2, x7\nHALT\n.end\n";
+    for format in ["--format=bits", "--format=hex"] {
+        let buffered = run(&[format, "--no-comments"], source);
+        let streamed = run(&[format, "--stream"], source);
+        assert!(streamed.status.success());
+        assert_eq!(stdout(&streamed), stdout(&buffered));
+    }
+
+    let path = source_file("stream", source);
+    let buffered = run(&["--format=bin", path.to_str().unwrap()], "");
+    assert!(buffered.status.success());
+    let expected = std::fs::read(path.with_extension("obj")).unwrap();
+    std::fs::remove_file(path.with_extension("obj")).unwrap();
+    let streamed = run(&["--format=bin", "--stream", path.to_str().unwrap()], "");
+    assert!(streamed.status.success());
+    assert_eq!(stdout(&streamed), "");
+    assert_eq!(std::fs::read(path.with_extension("obj")).unwrap(), expected);
+
+    // an error partway through leaves no object file, as it does when nothing is streamed
+    let path = source_file("stream_error", ".orig x3000\nHALT\nHALT\nBR NOWHERE\n.end\n");
+    for args in [&["--format=bin"][..], &["--format=bin", "--stream"]] {
+        let output = run(&[args, &[path.to_str().unwrap()]].concat(), "");
+        assert_eq!(output.status.code(), Some(1));
+        assert!(!path.with_extension("obj").exists());
+        assert!(!path.with_extension("obj.partial").exists());
+    }
+}
+
+#[test]
+fn stream_rejects_flags_that_need_the_whole_program() {
+    for flag in ["--check", "--checksum", "--max-words=10", "--entry=MAIN", "--sym", "--lst", "--with-address"] {
+        let output = run(&["--stream", flag], ".orig x3000\nMAIN HALT\n.end\n");
+        assert_eq!(output.status.code(), Some(2));
+        let name = flag.split('=').next().unwrap();
+        assert!(stderr(&output).contains(&format!("{} can't be used with --stream", name)), "{}", flag);
+    }
+}
+
+// the bytes of intel hex records by address, checking each record's checksum along the way
+fn decode_ihex(records: &str) -> Vec<(u32, u8)> {
+    let mut bytes = Vec::new();
+    let mut upper = 0;
+    for record in records.lines() {
+        let record = record.strip_prefix(':').unwrap();
+        let fields = (0..record.len()).step_by(2).map(|i| u8::from_str_radix(&record[i..i + 2], 16).unwrap()).collect::<Vec<_>>();
+        assert_eq!(fields.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)), 0, "{}", record);
+        let offset = u16::from_be_bytes([fields[1], fields[2]]) as u32;
+        let data = &fields[4..4 + fields[0] as usize];
+        match fields[3] {
+            0x00 => bytes.extend(data.iter().enumerate().map(|(i, byte)| (upper + offset + i as u32, *byte))),
+            0x01 => break,
+            0x04 => upper = (u16::from_be_bytes([data[0], data[1]]) as u32) << 16,
+            kind => panic!("unexpected record type {}", kind),
+        }
+    }
+    bytes
+}
+
+#[test]
+fn ihex_records_decode_to_the_words() {
+    let output = run(&["--format=ihex"], ".orig x3000\nHALT\n.fill x1234\n.end\n");
+    assert!(stdout(&output).ends_with(":00000001FF\n"));
+    assert_eq!(decode_ihex(&stdout(&output)), [(0x6000, 0xF0), (0x6001, 0x25), (0x6002, 0x12), (0x6003, 0x34)]);
+
+    // byte addresses past xFFFF need an extended linear address
+    let output = run(&["--format=ihex"], ".orig x8000\n.fill xABCD\n.end\n");
+    assert_eq!(decode_ihex(&stdout(&output)), [(0x10000, 0xAB), (0x10001, 0xCD)]);
+}
+
+#[test]
+fn max_errors_caps_what_is_reported() {
+    let source = format!(".orig x3000\n{}.end\n", "HALT R0\n".repeat(30));
+    let output = run(&["--max-errors=5"], &source);
+    assert_eq!(output.status.code(), Some(1));
+    let reported = stderr(&output);
+    assert_eq!(reported.lines().filter(|line| line.starts_with("error: ")).count(), 5);
+    assert!(reported.ends_with("error: line 6: HALT takes no operands\n6 | HALT R0\n... and 25 more errors\n"));
+}
+
+#[test]
+fn with_address_counts_every_word_of_a_directive() {
+    let output = run(&["--with-address", "--format=hex"], ".orig x3000\nHALT\n.stringz \"ab\"\n.blkw 2\nADD R0 R0 R0\n.end\n");
+    assert_eq!(
+        stdout(&output),
+        concat!(
+            "       0x3000 // .orig x3000\n",
+            "x3000: 0xF025 // HALT\n",
+            "x3001: 0x0061 // .stringz \"ab\"\n",
+            "x3002: 0x0062 // .stringz \"ab\"\n",
+            "x3003: 0x0000 // .stringz \"ab\"\n",
+            "x3004: 0x0000 // .blkw 2\n",
+            "x3005: 0x0000 // .blkw 2\n",
+            "x3006: 0x1000 // ADD R0 R0 R0\n",
+        ),
+    );
+}
+
+#[test]
+fn echo_is_the_line_as_written() {
+    let output = run(&["--format=hex"], ".orig x3000\n.stringz \"Hi\" ; Greeting\n.end\n");
+    assert_eq!(
+        stdout(&output),
+        "0x3000 // .orig x3000\n0x0048 // .stringz \"Hi\" ; Greeting\n0x0069 // .stringz \"Hi\" ; Greeting\n0x0000 // .stringz \"Hi\" ; Greeting\n",
+    );
+}
+
+#[test]
+fn origin_from_the_command_line_has_a_row_without_source() {
+    let path = source_file("listing_origin", "; boot\nSTART HALT\n.fill START\n.end\n");
+    let output = run(&["--origin=x4000", "--lst", "--format=hex", path.to_str().unwrap()], "");
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "0x4000\n0xF025 // START HALT\n0x4000 // .fill START\n");
+    assert_eq!(
+        std::fs::read_to_string(path.with_extension("lst")).unwrap(),
+        concat!(
+            "      4000  0100000000000000\n",
+            "                              ; boot\n",
+            "4000  F025  1111000000100101  START HALT\n",
+            "4001  4000  0100000000000000  .fill START\n",
+            "                              .end\n",
+        ),
+    );
+}